@@ -76,5 +76,43 @@ fn on_gc(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, on_length, on_gc);
+fn reverse_on_length(c: &mut Criterion) {
+    let mut g = c.benchmark_group("reverse_complement/length");
+
+    g.sample_size(100);
+    g.warm_up_time(std::time::Duration::from_secs(1));
+
+    for len in (1_000..10_000)
+        .step_by(1_000)
+        .chain((10_000..=100_000).step_by(10_000))
+    {
+        let bits = utils::get_bit(len, 0.5);
+
+        g.bench_with_input(BenchmarkId::new("scalar", len), &bits, |b, bits| {
+            b.iter(|| {
+                nuc2bit::complement::pub_reverse_complement_scalar(bits, len);
+            })
+        });
+
+        g.bench_with_input(BenchmarkId::new("avx", len), &bits, |b, bits| {
+            b.iter(|| unsafe {
+                nuc2bit::complement::pub_reverse_complement_avx(bits, len);
+            })
+        });
+
+        g.bench_with_input(BenchmarkId::new("sse", len), &bits, |b, bits| {
+            b.iter(|| unsafe {
+                nuc2bit::complement::pub_reverse_complement_sse(bits, len);
+            })
+        });
+
+        g.bench_with_input(BenchmarkId::new("pub", len), &bits, |b, bits| {
+            b.iter(|| {
+                nuc2bit::complement::reverse_complement(bits, len);
+            })
+        });
+    }
+}
+
+criterion_group!(benches, on_length, on_gc, reverse_on_length);
 criterion_main!(benches);
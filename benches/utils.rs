@@ -6,7 +6,7 @@ pub fn get_nuc(length: usize, gc: f64) -> Vec<u8> {
     let dna = [b'A', b'T', b'C', b'G'];
     let prob = [1.0 - gc, 1.0 - gc, gc, gc];
 
-    let dist = rand::distributions::WeightedIndex::new(&prob).unwrap(); // value of weight can't be negative
+    let dist = rand::distributions::WeightedIndex::new(prob.iter()).unwrap(); // value of weight can't be negative
 
     let mut seq = Vec::with_capacity(length);
 
@@ -17,6 +17,8 @@ pub fn get_nuc(length: usize, gc: f64) -> Vec<u8> {
     seq
 }
 
+// not every bench that includes this module packs its input
+#[allow(dead_code)]
 pub fn get_bit(length: usize, gc: f64) -> Vec<u64> {
     nuc2bit::nuc2bit::encode(&get_nuc(length, gc))
 }
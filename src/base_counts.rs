@@ -0,0 +1,486 @@
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use crate::utils::word_at;
+
+// the number of each nucleotide in the first `len` nucleotides, in the order of the codes:
+// A, C, T, G
+pub fn base_counts(bits: &[u64], len: usize) -> [usize; 4] {
+    if len > (bits.len() * 32) {
+        panic!(
+            "The length {} is greater than the number of nucleotides!",
+            len
+        );
+    }
+
+    counts(planes(bits, len), len)
+}
+
+// the fraction of C and G in the first `len` nucleotides, 0 for an empty sequence
+pub fn gc_content(bits: &[u64], len: usize) -> f64 {
+    if len == 0 {
+        return 0.0;
+    }
+
+    let counts = base_counts(bits, len);
+
+    (counts[1] + counts[3]) as f64 / len as f64
+}
+
+// the number of low bits, high bits and both bits set
+fn planes(bits: &[u64], len: usize) -> [usize; 3] {
+    if len / 64 <= 8 {
+        return planes_scalar(bits, len);
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { planes_avx(bits, len) };
+        } else if is_x86_feature_detected!("ssse3") {
+            return unsafe { planes_sse(bits, len) };
+        }
+    }
+
+    planes_scalar(bits, len)
+}
+
+#[inline]
+fn counts(planes: [usize; 3], len: usize) -> [usize; 4] {
+    let [lo, hi, both] = planes;
+    let g = both;
+    let c = lo - both;
+    let t = hi - both;
+
+    [len - c - t - g, c, t, g]
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn planes_avx(bits: &[u64], len: usize) -> [usize; 3] {
+    let end_idx = (((len / 32) / 8) / 2) / 4;
+
+    let mut res = [_mm256_setzero_si256(); 3];
+    let mut acc = [_mm256_setzero_si256(); 3];
+    let ptr = bits.as_ptr() as *const __m256i;
+
+    // for each bit pattern of two nucleotides, the number of low bits, high bits and 0b11 set
+    let luts = [
+        _mm256_set_epi64x(
+            0x0201020101000100,
+            0x0201020101000100,
+            0x0201020101000100,
+            0x0201020101000100,
+        ),
+        _mm256_set_epi64x(
+            0x0202010102020101,
+            0x0101000001010000,
+            0x0202010102020101,
+            0x0101000001010000,
+        ),
+        _mm256_set_epi64x(
+            0x0201010101000000,
+            0x0100000001000000,
+            0x0201010101000000,
+            0x0100000001000000,
+        ),
+    ];
+    let mask = _mm256_set1_epi8(0x0F);
+
+    let mut idx = 0;
+
+    for _i in 0..end_idx {
+        for _j in 0..16 {
+            let v = _mm256_loadu_si256(ptr.offset(idx));
+            let lo = _mm256_and_si256(v, mask);
+            let hi = _mm256_and_si256(_mm256_srli_epi16(v, 4), mask);
+
+            for k in 0..3 {
+                let count = _mm256_add_epi8(
+                    _mm256_shuffle_epi8(luts[k], lo),
+                    _mm256_shuffle_epi8(luts[k], hi),
+                );
+                acc[k] = _mm256_add_epi8(acc[k], count);
+            }
+
+            idx += 1;
+        }
+
+        for k in 0..3 {
+            res[k] = _mm256_add_epi64(res[k], _mm256_sad_epu8(acc[k], _mm256_setzero_si256()));
+            acc[k] = _mm256_setzero_si256();
+        }
+    }
+
+    union A {
+        v: __m256i,
+        a: [u64; 4],
+    }
+
+    let end = end_idx * 8 * 2 * 4;
+    let mut planes = planes_scalar(&bits[end..], len - (end * 32));
+
+    for k in 0..3 {
+        let a = A { v: res[k] };
+        planes[k] += ((a.a[0] + a.a[1]) + (a.a[2] + a.a[3])) as usize;
+    }
+
+    planes
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+unsafe fn planes_sse(bits: &[u64], len: usize) -> [usize; 3] {
+    let end_idx = (((len / 32) / 8) / 2) / 2;
+
+    let mut res = [_mm_setzero_si128(); 3];
+    let mut acc = [_mm_setzero_si128(); 3];
+    let ptr = bits.as_ptr() as *const __m128i;
+
+    let luts = [
+        _mm_set_epi64x(0x0201020101000100, 0x0201020101000100),
+        _mm_set_epi64x(0x0202010102020101, 0x0101000001010000),
+        _mm_set_epi64x(0x0201010101000000, 0x0100000001000000),
+    ];
+    let mask = _mm_set1_epi8(0x0F);
+
+    let mut idx = 0;
+
+    for _i in 0..end_idx {
+        for _j in 0..16 {
+            let v = _mm_loadu_si128(ptr.offset(idx));
+            let lo = _mm_and_si128(v, mask);
+            let hi = _mm_and_si128(_mm_srli_epi16(v, 4), mask);
+
+            for k in 0..3 {
+                let count =
+                    _mm_add_epi8(_mm_shuffle_epi8(luts[k], lo), _mm_shuffle_epi8(luts[k], hi));
+                acc[k] = _mm_add_epi8(acc[k], count);
+            }
+
+            idx += 1;
+        }
+
+        for k in 0..3 {
+            res[k] = _mm_add_epi64(res[k], _mm_sad_epu8(acc[k], _mm_setzero_si128()));
+            acc[k] = _mm_setzero_si128();
+        }
+    }
+
+    union A {
+        v: __m128i,
+        a: [u64; 2],
+    }
+
+    let end = end_idx * 8 * 2 * 2;
+    let mut planes = planes_scalar(&bits[end..], len - (end * 32));
+
+    for k in 0..3 {
+        let a = A { v: res[k] };
+        planes[k] += (a.a[0] + a.a[1]) as usize;
+    }
+
+    planes
+}
+
+fn planes_scalar(bits: &[u64], len: usize) -> [usize; 3] {
+    let mut res = [0usize; 3];
+    let end_idx = len / 32;
+
+    let mask = 0x5555555555555555u64; // 0b...01010101
+
+    let mut add = |w: u64| {
+        let lo = w & mask;
+        let hi = (w >> 1) & mask;
+
+        res[0] += lo.count_ones() as usize;
+        res[1] += hi.count_ones() as usize;
+        res[2] += (lo & hi).count_ones() as usize;
+    };
+
+    for i in 0..end_idx {
+        add(unsafe { *bits.get_unchecked(i) });
+    }
+
+    let leftover = (len % 32) * 2;
+
+    if leftover > 0 {
+        add(unsafe { *bits.get_unchecked(end_idx) & ((1 << leftover) - 1) });
+    }
+
+    res
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GcWindow {
+    pub start: usize,
+    // fraction of C and G
+    pub gc: f64,
+    // (G - C) / (G + C), 0 without C nor G
+    pub skew: f64,
+}
+
+// the GC content and skew of the windows of `size` nucleotides starting every `step`
+// nucleotides, a window only counts the nucleotides that enter and leave it when it moves
+pub struct GcWindows<'a> {
+    bits: &'a [u64],
+    len: usize,
+    size: usize,
+    step: usize,
+    start: usize,
+    // C and G in the current window, `None` before the first one
+    counts: Option<[usize; 2]>,
+}
+
+impl<'a> GcWindows<'a> {
+    pub fn new(bits: &'a [u64], len: usize, size: usize, step: usize) -> Self {
+        if len > (bits.len() * 32) {
+            panic!(
+                "The length {} is greater than the number of nucleotides!",
+                len
+            );
+        }
+
+        if size == 0 || step == 0 {
+            panic!("The window size and step must be at least 1!");
+        }
+
+        GcWindows {
+            bits,
+            len,
+            size,
+            step,
+            start: 0,
+            counts: None,
+        }
+    }
+}
+
+impl<'a> Iterator for GcWindows<'a> {
+    type Item = GcWindow;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let counts = match self.counts {
+            None => {
+                if self.size > self.len {
+                    return None;
+                }
+
+                gc_counts(self.bits, 0, self.size)
+            }
+            Some([c, g]) => {
+                let start = self.start + self.step;
+
+                if start + self.size > self.len {
+                    return None;
+                }
+
+                let counts = if self.step < self.size {
+                    let [c_out, g_out] = gc_counts(self.bits, self.start, start);
+                    let [c_in, g_in] =
+                        gc_counts(self.bits, self.start + self.size, start + self.size);
+
+                    [c - c_out + c_in, g - g_out + g_in]
+                } else {
+                    gc_counts(self.bits, start, start + self.size)
+                };
+
+                self.start = start;
+                counts
+            }
+        };
+
+        self.counts = Some(counts);
+
+        let [c, g] = counts;
+
+        Some(GcWindow {
+            start: self.start,
+            gc: (c + g) as f64 / self.size as f64,
+            skew: if c + g == 0 {
+                0.0
+            } else {
+                (g as f64 - c as f64) / (g + c) as f64
+            },
+        })
+    }
+}
+
+// the number of C and G in the nucleotides `from..to`, 32 at a time
+fn gc_counts(bits: &[u64], from: usize, to: usize) -> [usize; 2] {
+    let mut res = [0usize; 2];
+    let mut pos = from;
+
+    let mask = 0x5555555555555555u64; // 0b...01010101
+
+    while pos < to {
+        let n = std::cmp::min(to - pos, 32);
+        let mut w = word_at(bits, pos);
+
+        if n < 32 {
+            w &= (1 << (n * 2)) - 1;
+        }
+
+        let lo = w & mask;
+        let hi = (w >> 1) & mask;
+
+        res[0] += (lo & !hi).count_ones() as usize;
+        res[1] += (lo & hi).count_ones() as usize;
+
+        pos += n;
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nuc2bit::encode;
+    use crate::utils::random_nuc;
+
+    fn naive(nuc: &[u8]) -> [usize; 4] {
+        let count = |b: u8| nuc.iter().filter(|&&n| n == b).count();
+        [count(b'A'), count(b'C'), count(b'T'), count(b'G')]
+    }
+
+    #[test]
+    fn test_base_counts_scalar() {
+        assert_eq!(
+            counts(planes_scalar(&encode(b"AACGTTTGG"), 9), 9),
+            [2, 1, 3, 3]
+        );
+        // the nucleotides after `len` are ignored
+        assert_eq!(counts(planes_scalar(&encode(b"ACGTG"), 3), 3), [1, 1, 0, 1]);
+
+        for &len in &[0, 1, 31, 32, 33, 100] {
+            let nuc = random_nuc(len, 3);
+            assert_eq!(counts(planes_scalar(&encode(&nuc), len), len), naive(&nuc));
+        }
+    }
+
+    #[test]
+    fn test_base_counts_avx() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                for &len in &[0, 100, 4096, 4190, 9000] {
+                    let nuc = random_nuc(len, 3);
+                    assert_eq!(
+                        counts(unsafe { planes_avx(&encode(&nuc), len) }, len),
+                        naive(&nuc)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_base_counts_sse() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("ssse3") {
+                for &len in &[0, 100, 2048, 4190, 9000] {
+                    let nuc = random_nuc(len, 3);
+                    assert_eq!(
+                        counts(unsafe { planes_sse(&encode(&nuc), len) }, len),
+                        naive(&nuc)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_gc_content() {
+        assert_eq!(gc_content(&encode(b"ACGTACGT"), 8), 0.5);
+        assert_eq!(gc_content(&encode(b"GGGC"), 4), 1.0);
+        assert_eq!(gc_content(&[], 0), 0.0);
+
+        let nuc = random_nuc(5000, 3);
+        let counts = naive(&nuc);
+        assert_eq!(base_counts(&encode(&nuc), 5000), counts);
+        assert_eq!(
+            gc_content(&encode(&nuc), 5000),
+            (counts[1] + counts[3]) as f64 / 5000.0
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The length 40 is greater than the number of nucleotides!")]
+    fn test_base_counts_too_long() {
+        base_counts(&[0], 40);
+    }
+
+    fn naive_windows(nuc: &[u8], size: usize, step: usize) -> Vec<GcWindow> {
+        let mut res = Vec::new();
+        let mut start = 0;
+
+        while start + size <= nuc.len() {
+            let window = &nuc[start..(start + size)];
+            let c = window.iter().filter(|&&n| n == b'C').count() as f64;
+            let g = window.iter().filter(|&&n| n == b'G').count() as f64;
+
+            res.push(GcWindow {
+                start,
+                gc: (c + g) / size as f64,
+                skew: if c + g == 0.0 { 0.0 } else { (g - c) / (g + c) },
+            });
+            start += step;
+        }
+
+        res
+    }
+
+    #[test]
+    fn test_gc_windows() {
+        let nuc = random_nuc(500, 3);
+        let bits = encode(&nuc);
+
+        for &size in &[1, 7, 32, 33, 100, 500, 501] {
+            for &step in &[1, 5, 32, 100, 600] {
+                let res = GcWindows::new(&bits, nuc.len(), size, step).collect::<Vec<_>>();
+                let expected = naive_windows(&nuc, size, step);
+
+                assert_eq!(res.len(), expected.len());
+
+                for (r, e) in res.iter().zip(expected.iter()) {
+                    assert_eq!(r.start, e.start);
+                    assert!((r.gc - e.gc).abs() < 1e-12);
+                    assert!((r.skew - e.skew).abs() < 1e-12);
+                }
+            }
+        }
+
+        let res = GcWindows::new(&encode(b"GGGCAAAATTTTAC"), 14, 4, 4).collect::<Vec<_>>();
+        assert_eq!(
+            res,
+            vec![
+                GcWindow {
+                    start: 0,
+                    gc: 1.0,
+                    skew: 0.5
+                },
+                GcWindow {
+                    start: 4,
+                    gc: 0.0,
+                    skew: 0.0
+                },
+                GcWindow {
+                    start: 8,
+                    gc: 0.0,
+                    skew: 0.0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The window size and step must be at least 1!")]
+    fn test_gc_windows_empty() {
+        GcWindows::new(&[0], 32, 10, 0);
+    }
+}
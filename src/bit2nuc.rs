@@ -4,33 +4,87 @@ use std::arch::x86::*;
 #[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::*;
 
-use std::alloc;
+use crate::nuc2bit::Block;
+use crate::utils::word_at;
 
 pub fn decode(bits: &[u64], len: usize) -> Vec<u8> {
-    if len > (bits.len() * 32) {
+    let mut nuc = Vec::new();
+    decode_into(bits, len, &mut nuc);
+    nuc
+}
+
+// the content of `nuc` is replaced by the decoded nucleotides, reusing its allocation
+pub fn decode_into(bits: &[u64], len: usize, nuc: &mut Vec<u8>) {
+    decode_at_into(bits, 0, len, nuc)
+}
+
+// the blocks are the ones returned by `nuc2bit::encode_with_n_blocks`
+pub fn decode_with_n_blocks(bits: &[u64], len: usize, blocks: &[Block]) -> Vec<u8> {
+    let mut nuc = decode(bits, len);
+    insert_n_blocks(&mut nuc, blocks);
+    nuc
+}
+
+pub fn insert_n_blocks(nuc: &mut [u8], blocks: &[Block]) {
+    for block in blocks {
+        for n in &mut nuc[block.start..(block.start + block.len)] {
+            *n = b'N';
+        }
+    }
+}
+
+// the blocks are the ones returned by `nuc2bit::encode_with_mask_blocks`
+pub fn decode_with_mask_blocks(bits: &[u64], len: usize, blocks: &[Block]) -> Vec<u8> {
+    let mut nuc = decode(bits, len);
+    apply_mask_blocks(&mut nuc, blocks);
+    nuc
+}
+
+pub fn apply_mask_blocks(nuc: &mut [u8], blocks: &[Block]) {
+    for block in blocks {
+        nuc[block.start..(block.start + block.len)].make_ascii_lowercase();
+    }
+}
+
+// decode `len` nucleotides starting at nucleotide `offset` of `bits`
+pub(crate) fn decode_at(bits: &[u64], offset: usize, len: usize) -> Vec<u8> {
+    let mut nuc = Vec::new();
+    decode_at_into(bits, offset, len, &mut nuc);
+    nuc
+}
+
+pub(crate) fn decode_at_into(bits: &[u64], offset: usize, len: usize, nuc: &mut Vec<u8>) {
+    if offset + len > (bits.len() * 32) {
         panic!(
             "The length {} is greater than the number of nucleotides!",
-            len
+            offset + len
         );
     }
 
+    let bits = &bits[(offset / 32)..];
+    let shift = offset % 32;
+
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     {
         if is_x86_feature_detected!("avx2") {
-            return unsafe { decode_shuffle_avx(bits, len) };
+            return unsafe { decode_shuffle_avx(bits, shift, len, nuc) };
         } else if is_x86_feature_detected!("sse4.1") {
-            return unsafe { decode_shuffle_sse(bits, len) };
+            return unsafe { decode_shuffle_sse(bits, shift, len, nuc) };
         }
     }
 
-    decode_lut(bits, len)
+    decode_lut(bits, shift, len, nuc)
 }
 
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 #[target_feature(enable = "avx2")]
-unsafe fn decode_shuffle_avx(bits: &[u64], len: usize) -> Vec<u8> {
-    let layout = alloc::Layout::from_size_align_unchecked(bits.len() * 32, 32);
-    let ptr = alloc::alloc(layout) as *mut __m256i;
+unsafe fn decode_shuffle_avx(bits: &[u64], shift: usize, len: usize, nuc: &mut Vec<u8>) {
+    let words = (len / 32) + if len % 32 == 0 { 0 } else { 1 };
+
+    // whole words are decoded, so there may be up to 31 extra bytes after the end
+    nuc.clear();
+    nuc.reserve(words * 32);
+    let ptr = nuc.as_mut_ptr() as *mut __m256i;
 
     let shuffle_mask = _mm256_set_epi32(
         0x07070707, 0x06060606, 0x05050505, 0x04040404, 0x03030303, 0x02020202, 0x01010101,
@@ -50,8 +104,8 @@ unsafe fn decode_shuffle_avx(bits: &[u64], len: usize) -> Vec<u8> {
         lut_i32,
     );
 
-    for i in 0..bits.len() {
-        let curr = *bits.get_unchecked(i) as i64;
+    for i in 0..words {
+        let curr = word_at(bits, shift + i * 32) as i64;
         let v = _mm256_set1_epi64x(curr);
 
         // duplicate each byte four times
@@ -69,19 +123,21 @@ unsafe fn decode_shuffle_avx(bits: &[u64], len: usize) -> Vec<u8> {
 
         // use lookup table to convert nucleotide bits to bytes
         let v = _mm256_shuffle_epi8(lut, v);
-        _mm256_store_si256(ptr.offset(i as isize), v);
+        _mm256_storeu_si256(ptr.offset(i as isize), v);
     }
 
-    Vec::from_raw_parts(ptr as *mut u8, len, bits.len() * 32)
+    nuc.set_len(len);
 }
 
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 #[target_feature(enable = "sse4.1")]
-unsafe fn decode_shuffle_sse(bits: &[u64], len: usize) -> Vec<u8> {
-    let layout = alloc::Layout::from_size_align_unchecked(bits.len() * 32, 16);
-    let ptr = alloc::alloc(layout) as *mut __m128i;
+unsafe fn decode_shuffle_sse(bits: &[u64], shift: usize, len: usize, nuc: &mut Vec<u8>) {
+    let words = (len / 32) + if len % 32 == 0 { 0 } else { 1 };
 
-    let bits_ptr = bits.as_ptr() as *const i32;
+    // whole words are decoded, so there may be up to 31 extra bytes after the end
+    nuc.clear();
+    nuc.reserve(words * 32);
+    let ptr = nuc.as_mut_ptr() as *mut __m128i;
 
     let shuffle_mask = _mm_set_epi32(0x03030303, 0x02020202, 0x01010101, 0x00000000);
     let lo_mask = _mm_set1_epi16(0b0000110000000011);
@@ -89,8 +145,8 @@ unsafe fn decode_shuffle_sse(bits: &[u64], len: usize) -> Vec<u8> {
         (b'A' as i32) | ((b'C' as i32) << 8) | ((b'T' as i32) << 16) | ((b'G' as i32) << 24);
     let lut = _mm_set_epi32(b'G' as i32, b'T' as i32, b'C' as i32, lut_i32);
 
-    for i in 0..(bits.len() * 2) as isize {
-        let curr = *bits_ptr.offset(i);
+    for i in 0..(words * 2) as isize {
+        let curr = (word_at(bits, shift + (i as usize / 2) * 32) >> ((i & 1) * 32)) as i32;
         let v = _mm_set1_epi32(curr);
 
         // duplicate each byte four times
@@ -108,10 +164,10 @@ unsafe fn decode_shuffle_sse(bits: &[u64], len: usize) -> Vec<u8> {
 
         // use lookup table to convert nucleotide bits to bytes
         let v = _mm_shuffle_epi8(lut, v);
-        _mm_store_si128(ptr.offset(i), v);
+        _mm_storeu_si128(ptr.offset(i), v);
     }
 
-    Vec::from_raw_parts(ptr as *mut u8, len, bits.len() * 32)
+    nuc.set_len(len);
 }
 
 static BITS_LUT: [u8; 4] = {
@@ -123,39 +179,46 @@ static BITS_LUT: [u8; 4] = {
     lut
 };
 
-fn decode_lut(bits: &[u64], len: usize) -> Vec<u8> {
-    let layout = unsafe { alloc::Layout::from_size_align_unchecked(len, 1) };
-    let res_ptr = unsafe { alloc::alloc(layout) };
+fn decode_lut(bits: &[u64], shift: usize, len: usize, nuc: &mut Vec<u8>) {
+    nuc.clear();
+    nuc.reserve(len);
+    let res_ptr = nuc.as_mut_ptr();
 
     for i in 0..len {
-        let offset = i >> 5;
-        let shift = (i & 31) << 1;
+        let offset = (i + shift) >> 5;
+        let bit_shift = ((i + shift) & 31) << 1;
         let curr = unsafe { *bits.get_unchecked(offset) };
 
         unsafe {
             *res_ptr.offset(i as isize) =
-                *BITS_LUT.get_unchecked(((curr >> shift) & 0b11) as usize);
+                *BITS_LUT.get_unchecked(((curr >> bit_shift) & 0b11) as usize);
         }
     }
 
-    unsafe { Vec::from_raw_parts(res_ptr, len, len) }
+    unsafe { nuc.set_len(len) };
 }
 
 #[cfg(feature = "bench-internals")]
 pub fn pub_decode_lut(bits: &[u64], len: usize) -> Vec<u8> {
-    decode_lut(bits, len)
+    let mut nuc = Vec::new();
+    decode_lut(bits, 0, len, &mut nuc);
+    nuc
 }
 
 #[cfg(feature = "bench-internals")]
 #[target_feature(enable = "avx2")]
 pub unsafe fn pub_decode_avx(bits: &[u64], len: usize) -> Vec<u8> {
-    decode_shuffle_avx(bits, len)
+    let mut nuc = Vec::new();
+    decode_shuffle_avx(bits, 0, len, &mut nuc);
+    nuc
 }
 
 #[cfg(feature = "bench-internals")]
-#[target_feature(enable = "sse2")]
+#[target_feature(enable = "sse4.1")]
 pub unsafe fn pub_decode_sse(bits: &[u64], len: usize) -> Vec<u8> {
-    decode_shuffle_sse(bits, len)
+    let mut nuc = Vec::new();
+    decode_shuffle_sse(bits, 0, len, &mut nuc);
+    nuc
 }
 
 pub struct Decode<'a> {
@@ -176,12 +239,22 @@ impl<'a> Decode<'a> {
             len,
         }
     }
-}
 
-impl<'a> Iterator for Decode<'a> {
-    type Item = u8;
+    // start decoding at nucleotide `offset` of the first word
+    pub(crate) fn with_offset(bytes: &'a [u64], offset: usize, len: usize) -> Self {
+        let mut decode = Decode::new(bytes, len);
 
-    fn next(&mut self) -> Option<Self::Item> {
+        if offset % 32 > 0 && len > 0 {
+            decode.buffer = bytes[0] >> ((offset % 32) * 2);
+            decode.pos_in_array = 1;
+            decode.val_in_buffer = 32 - (offset % 32);
+        }
+
+        decode
+    }
+
+    #[inline]
+    fn next_code(&mut self) -> Option<u8> {
         if self.len == 0 {
             return None;
         }
@@ -192,12 +265,42 @@ impl<'a> Iterator for Decode<'a> {
             self.val_in_buffer = 32;
         }
 
-        let val = (self.buffer & 0b11) as usize;
+        let val = (self.buffer & 0b11) as u8;
         self.buffer >>= 2;
         self.val_in_buffer -= 1;
         self.len -= 1;
 
-        unsafe { Some(*BITS_LUT.get_unchecked(val)) }
+        Some(val)
+    }
+}
+
+impl<'a> Iterator for Decode<'a> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_code()
+            .map(|val| unsafe { *BITS_LUT.get_unchecked(val as usize) })
+    }
+}
+
+// same as `Decode`, but yields the 2-bit codes like `nuc2bit::Encode`
+pub struct Codes<'a> {
+    decode: Decode<'a>,
+}
+
+impl<'a> Codes<'a> {
+    pub fn new(bytes: &'a [u64], len: usize) -> Self {
+        Codes {
+            decode: Decode::new(bytes, len),
+        }
+    }
+}
+
+impl<'a> Iterator for Codes<'a> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.decode.next_code()
     }
 }
 
@@ -205,16 +308,37 @@ impl<'a> Iterator for Decode<'a> {
 mod tests {
     use super::*;
 
+    fn decode_with_lut(bits: &[u64], shift: usize, len: usize) -> Vec<u8> {
+        let mut nuc = Vec::new();
+        decode_lut(bits, shift, len, &mut nuc);
+        nuc
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    unsafe fn decode_with_avx(bits: &[u64], shift: usize, len: usize) -> Vec<u8> {
+        let mut nuc = Vec::new();
+        decode_shuffle_avx(bits, shift, len, &mut nuc);
+        nuc
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    unsafe fn decode_with_sse(bits: &[u64], shift: usize, len: usize) -> Vec<u8> {
+        let mut nuc = Vec::new();
+        decode_shuffle_sse(bits, shift, len, &mut nuc);
+        nuc
+    }
+
     #[test]
-    fn test_decode_lut() {
+    fn test_decode_with_lut() {
         assert_eq!(
-            decode_lut(
+            decode_with_lut(
                 &vec![0b1101100011011000110110001101100011011000110110001101100011011000],
+                0,
                 32
             ),
             b"ATCGATCGATCGATCGATCGATCGATCGATCG"
         );
-        assert_eq!(decode_lut(&vec![0b11011000], 4), b"ATCG");
+        assert_eq!(decode_with_lut(&vec![0b11011000], 0, 4), b"ATCG");
     }
 
     #[test]
@@ -224,16 +348,17 @@ mod tests {
             if is_x86_feature_detected!("avx2") {
                 assert_eq!(
                     unsafe {
-                        decode_shuffle_avx(
+                        decode_with_avx(
                             &vec![
                                 0b1101100011011000110110001101100011011000110110001101100011011000,
                             ],
+                            0,
                             32,
                         )
                     },
                     b"ATCGATCGATCGATCGATCGATCGATCGATCG"
                 );
-                assert_eq!(unsafe { decode_shuffle_avx(&vec![0b11011000], 4) }, b"ATCG");
+                assert_eq!(unsafe { decode_with_avx(&vec![0b11011000], 0, 4) }, b"ATCG");
             }
         }
     }
@@ -245,18 +370,110 @@ mod tests {
             if is_x86_feature_detected!("sse4.1") {
                 assert_eq!(
                     unsafe {
-                        decode_shuffle_sse(
+                        decode_with_sse(
                             &vec![
                                 0b1101100011011000110110001101100011011000110110001101100011011000,
                             ],
+                            0,
                             32,
                         )
                     },
                     b"ATCGATCGATCGATCGATCGATCGATCGATCG"
                 );
-                assert_eq!(unsafe { decode_shuffle_sse(&vec![0b11011000], 4) }, b"ATCG");
+                assert_eq!(unsafe { decode_with_sse(&vec![0b11011000], 0, 4) }, b"ATCG");
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_offset() {
+        let bits = vec![
+            0b1101100011011000110110001101100011011000110110001101100011011000,
+            0b1101100011011000110110001101100011011000110110001101100011011000,
+        ];
+
+        assert_eq!(decode_with_lut(&bits, 1, 6), b"TCGATC");
+        assert_eq!(decode_with_lut(&bits, 26, 36), b"CGATCGATCGATCGATCGATCGATCGATCGATCGAT");
+
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                assert_eq!(unsafe { decode_with_avx(&bits, 1, 6) }, b"TCGATC");
+                assert_eq!(
+                    unsafe { decode_with_avx(&bits, 26, 36) },
+                    b"CGATCGATCGATCGATCGATCGATCGATCGATCGAT"
+                );
+            }
+
+            if is_x86_feature_detected!("sse4.1") {
+                assert_eq!(unsafe { decode_with_sse(&bits, 1, 6) }, b"TCGATC");
+                assert_eq!(
+                    unsafe { decode_with_sse(&bits, 26, 36) },
+                    b"CGATCGATCGATCGATCGATCGATCGATCGATCGAT"
+                );
             }
         }
+
+        assert_eq!(decode_at(&bits, 33, 3), b"TCG");
+    }
+
+    #[test]
+    fn test_decode_into() {
+        let mut nuc = b"TTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTT".to_vec();
+
+        decode_into(&vec![0b11011000], 4, &mut nuc);
+        assert_eq!(nuc, b"ATCG");
+
+        decode_into(&vec![0b11011000, 0], 0, &mut nuc);
+        assert!(nuc.is_empty());
+
+        decode_into(&vec![], 0, &mut nuc);
+        assert!(nuc.is_empty());
+
+        decode_into(
+            &vec![0b1101100011011000110110001101100011011000110110001101100011011000, 0b11011000],
+            35,
+            &mut nuc,
+        );
+        assert_eq!(nuc, b"ATCGATCGATCGATCGATCGATCGATCGATCGATC");
+    }
+
+    #[test]
+    fn test_decode_with_n_blocks() {
+        assert_eq!(
+            decode_with_n_blocks(
+                &vec![0b11011000110110001101100011011000],
+                16,
+                &[Block { start: 0, len: 2 }, Block { start: 9, len: 6 }]
+            ),
+            b"NNCGATCGANNNNNNG"
+        );
+        assert_eq!(decode_with_n_blocks(&vec![0b11011000], 4, &[]), b"ATCG");
+    }
+
+    #[test]
+    fn test_decode_with_mask_blocks() {
+        assert_eq!(
+            decode_with_mask_blocks(
+                &vec![0b11011000110110001101100011011000],
+                16,
+                &[Block { start: 0, len: 2 }, Block { start: 9, len: 6 }]
+            ),
+            b"atCGATCGAtcgatcG"
+        );
+
+        let mut nuc = decode_with_n_blocks(&vec![0b11011000], 4, &[Block { start: 1, len: 2 }]);
+        apply_mask_blocks(&mut nuc, &[Block { start: 0, len: 2 }]);
+        assert_eq!(nuc, b"anNG");
+    }
+
+    #[test]
+    fn test_codes_iterator() {
+        assert_eq!(
+            vec![0, 2, 1, 3, 0, 2],
+            Codes::new(&vec![0b11011000110110001101100011011000], 6).collect::<Vec<u8>>()
+        );
+        assert_eq!(Codes::new(&vec![], 0).count(), 0);
     }
 
     #[test]
@@ -274,5 +491,10 @@ mod tests {
             b"ATCG".to_vec(),
             Decode::new(&vec![0b11011000], 4).collect::<Vec<u8>>()
         );
+
+        assert_eq!(
+            b"CGATC".to_vec(),
+            Decode::with_offset(&vec![0b1101100011011000, 0], 2, 5).collect::<Vec<u8>>()
+        );
     }
 }
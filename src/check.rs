@@ -23,34 +23,13 @@ unsafe fn check_avx(nuc: &[u8]) -> bool {
     let ptr = nuc.as_ptr() as *const __m256i;
     let end_idx = nuc.len() / 32;
 
-    let lut = {
-        let mut lut_hi = 0i64;
-        lut_hi |= 1i64 << ((b'A' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b'T' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b'U' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b'C' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b'G' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b'a' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b't' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b'u' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b'c' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b'g' as i64) - 64i64);
-        _mm256_set_epi64x(!lut_hi, -1i64, !lut_hi, -1i64)
-    };
-    let shift_lut = _mm256_set1_epi64x(0x8040201008040201u64 as i64);
-    let mask = _mm256_set1_epi8(0b00001111);
+    let lut = lut_avx();
 
     for i in 0..end_idx as isize {
-        let v = _mm256_loadu_si256(ptr.offset(i));
-        // use high 4 bits to lookup 8-bit chunk
-        let hi = _mm256_and_si256(_mm256_srli_epi16(v, 3), mask);
-        let hi_lut = _mm256_shuffle_epi8(lut, hi);
-        let lo_lut = _mm256_shuffle_epi8(shift_lut, v); // convert byte x into (1 << x)
-        // separately handle bytes where MSB is 1
-        let lo_lut = _mm256_or_si256(lo_lut, _mm256_cmpgt_epi8(_mm256_setzero_si256(), v));
-
-        // check if (lo_lut & hi_lut) has any ones
-        if _mm256_testz_si256(lo_lut, hi_lut) == 0 {
+        let v = invalid_avx(lut, _mm256_loadu_si256(ptr.offset(i)));
+
+        // check if any byte is invalid
+        if _mm256_testz_si256(v, v) == 0 {
             return false;
         }
     }
@@ -69,34 +48,13 @@ unsafe fn check_sse(nuc: &[u8]) -> bool {
     let ptr = nuc.as_ptr() as *const __m128i;
     let end_idx = nuc.len() / 16;
 
-    let lut = {
-        let mut lut_hi = 0i64;
-        lut_hi |= 1i64 << ((b'A' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b'T' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b'U' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b'C' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b'G' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b'a' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b't' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b'u' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b'c' as i64) - 64i64);
-        lut_hi |= 1i64 << ((b'g' as i64) - 64i64);
-        _mm_set_epi64x(!lut_hi, -1i64)
-    };
-    let shift_lut = _mm_set1_epi64x(0x8040201008040201u64 as i64);
-    let mask = _mm_set1_epi8(0b00001111);
+    let lut = lut_sse();
 
     for i in 0..end_idx as isize {
-        let v = _mm_loadu_si128(ptr.offset(i));
-        // use high 4 bits to lookup 8-bit chunk
-        let hi = _mm_and_si128(_mm_srli_epi16(v, 3), mask);
-        let hi_lut = _mm_shuffle_epi8(lut, hi);
-        let lo_lut = _mm_shuffle_epi8(shift_lut, v); // convert byte x into (1 << x)
-        // separately handle bytes where MSB is 1
-        let lo_lut = _mm_or_si128(lo_lut, _mm_cmpgt_epi8(_mm_setzero_si128(), v));
-
-        // check if (lo_lut & hi_lut) has any ones
-        if _mm_testz_si128(lo_lut, hi_lut) == 0 {
+        let v = invalid_sse(lut, _mm_loadu_si128(ptr.offset(i)));
+
+        // check if any byte is invalid
+        if _mm_testz_si128(v, v) == 0 {
             return false;
         }
     }
@@ -109,7 +67,87 @@ unsafe fn check_sse(nuc: &[u8]) -> bool {
     true
 }
 
-static CHECK_LUT: [bool; 256] = {
+// for each 8-bit chunk of the bytes, a bitset of the bytes that are not nucleotides
+const LUT_HI: i64 = !((1i64 << ((b'A' as i64) - 64i64))
+    | (1i64 << ((b'T' as i64) - 64i64))
+    | (1i64 << ((b'U' as i64) - 64i64))
+    | (1i64 << ((b'C' as i64) - 64i64))
+    | (1i64 << ((b'G' as i64) - 64i64))
+    | (1i64 << ((b'a' as i64) - 64i64))
+    | (1i64 << ((b't' as i64) - 64i64))
+    | (1i64 << ((b'u' as i64) - 64i64))
+    | (1i64 << ((b'c' as i64) - 64i64))
+    | (1i64 << ((b'g' as i64) - 64i64)));
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+#[inline]
+pub(crate) unsafe fn lut_avx() -> __m256i {
+    _mm256_set_epi64x(LUT_HI, -1i64, LUT_HI, -1i64)
+}
+
+// the bytes of the result are not zero where the bytes of `v` are not nucleotides
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+#[inline]
+pub(crate) unsafe fn invalid_avx(lut: __m256i, v: __m256i) -> __m256i {
+    let shift_lut = _mm256_set1_epi64x(0x8040201008040201u64 as i64);
+    let mask = _mm256_set1_epi8(0b00001111);
+
+    // use high 4 bits to lookup 8-bit chunk
+    let hi = _mm256_and_si256(_mm256_srli_epi16(v, 3), mask);
+    let hi_lut = _mm256_shuffle_epi8(lut, hi);
+    let lo_lut = _mm256_shuffle_epi8(shift_lut, v); // convert byte x into (1 << x)
+    // separately handle bytes where MSB is 1
+    let lo_lut = _mm256_or_si256(lo_lut, _mm256_cmpgt_epi8(_mm256_setzero_si256(), v));
+
+    _mm256_and_si256(lo_lut, hi_lut)
+}
+
+// one bit per byte of `v`, set if the byte is not a nucleotide
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+#[inline]
+pub(crate) unsafe fn invalid_movemask_avx(lut: __m256i, v: __m256i) -> u32 {
+    let v = invalid_avx(lut, v);
+    !(_mm256_movemask_epi8(_mm256_cmpeq_epi8(v, _mm256_setzero_si256())) as u32)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+#[inline]
+pub(crate) unsafe fn lut_sse() -> __m128i {
+    _mm_set_epi64x(LUT_HI, -1i64)
+}
+
+// the bytes of the result are not zero where the bytes of `v` are not nucleotides
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+#[inline]
+pub(crate) unsafe fn invalid_sse(lut: __m128i, v: __m128i) -> __m128i {
+    let shift_lut = _mm_set1_epi64x(0x8040201008040201u64 as i64);
+    let mask = _mm_set1_epi8(0b00001111);
+
+    // use high 4 bits to lookup 8-bit chunk
+    let hi = _mm_and_si128(_mm_srli_epi16(v, 3), mask);
+    let hi_lut = _mm_shuffle_epi8(lut, hi);
+    let lo_lut = _mm_shuffle_epi8(shift_lut, v); // convert byte x into (1 << x)
+    // separately handle bytes where MSB is 1
+    let lo_lut = _mm_or_si128(lo_lut, _mm_cmpgt_epi8(_mm_setzero_si128(), v));
+
+    _mm_and_si128(lo_lut, hi_lut)
+}
+
+// one bit per byte of `v`, set if the byte is not a nucleotide
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+#[inline]
+pub(crate) unsafe fn invalid_movemask_sse(lut: __m128i, v: __m128i) -> u16 {
+    let v = invalid_sse(lut, v);
+    !(_mm_movemask_epi8(_mm_cmpeq_epi8(v, _mm_setzero_si128())) as u16)
+}
+
+pub(crate) static CHECK_LUT: [bool; 256] = {
     let mut lut = [true; 256];
     lut[b'A' as usize] = false;
     lut[b'T' as usize] = false;
@@ -149,6 +187,188 @@ fn check_scalar(nuc: &[u8]) -> bool {
     }
 }
 
+pub fn first_invalid(nuc: &[u8]) -> Option<usize> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { first_invalid_avx(nuc) };
+        } else if is_x86_feature_detected!("ssse3") {
+            return unsafe { first_invalid_sse(nuc) };
+        }
+    }
+
+    first_invalid_scalar(nuc)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn first_invalid_avx(nuc: &[u8]) -> Option<usize> {
+    for (i, chunk) in nuc.chunks(64).enumerate() {
+        let mask = mask_avx(chunk);
+
+        if mask != 0 {
+            return Some(i * 64 + mask.trailing_zeros() as usize);
+        }
+    }
+
+    None
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+unsafe fn first_invalid_sse(nuc: &[u8]) -> Option<usize> {
+    for (i, chunk) in nuc.chunks(64).enumerate() {
+        let mask = mask_sse(chunk);
+
+        if mask != 0 {
+            return Some(i * 64 + mask.trailing_zeros() as usize);
+        }
+    }
+
+    None
+}
+
+fn first_invalid_scalar(nuc: &[u8]) -> Option<usize> {
+    nuc.iter()
+        .position(|&n| unsafe { *CHECK_LUT.get_unchecked(n as usize) })
+}
+
+// one bit per byte, set if the byte is not a nucleotide, 64 bytes per word
+pub fn invalid_mask(nuc: &[u8]) -> Vec<u64> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { invalid_mask_avx(nuc) };
+        } else if is_x86_feature_detected!("ssse3") {
+            return unsafe { invalid_mask_sse(nuc) };
+        }
+    }
+
+    invalid_mask_scalar(nuc)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn invalid_mask_avx(nuc: &[u8]) -> Vec<u64> {
+    nuc.chunks(64).map(|chunk| mask_avx(chunk)).collect()
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+unsafe fn invalid_mask_sse(nuc: &[u8]) -> Vec<u64> {
+    nuc.chunks(64).map(|chunk| mask_sse(chunk)).collect()
+}
+
+fn invalid_mask_scalar(nuc: &[u8]) -> Vec<u64> {
+    nuc.chunks(64).map(mask_scalar).collect()
+}
+
+// mask of the invalid bytes of at most 64 bytes
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn mask_avx(nuc: &[u8]) -> u64 {
+    if nuc.len() < 64 {
+        return mask_scalar(nuc);
+    }
+
+    let ptr = nuc.as_ptr() as *const __m256i;
+    let lut = lut_avx();
+
+    let lo = invalid_movemask_avx(lut, _mm256_loadu_si256(ptr.offset(0))) as u64;
+    let hi = invalid_movemask_avx(lut, _mm256_loadu_si256(ptr.offset(1))) as u64;
+
+    lo | (hi << 32)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+#[inline]
+unsafe fn mask_sse(nuc: &[u8]) -> u64 {
+    if nuc.len() < 64 {
+        return mask_scalar(nuc);
+    }
+
+    let ptr = nuc.as_ptr() as *const __m128i;
+    let lut = lut_sse();
+    let mut res = 0;
+
+    for i in 0..4 {
+        res |= (invalid_movemask_sse(lut, _mm_loadu_si128(ptr.offset(i))) as u64) << (i * 16);
+    }
+
+    res
+}
+
+fn mask_scalar(nuc: &[u8]) -> u64 {
+    let mut res = 0;
+
+    for i in 0..nuc.len() {
+        unsafe {
+            res |= (*CHECK_LUT.get_unchecked(*nuc.get_unchecked(i) as usize) as u64) << i;
+        }
+    }
+
+    res
+}
+
+pub fn invalid_positions(nuc: &[u8]) -> InvalidPositions<'_> {
+    InvalidPositions::new(nuc)
+}
+
+pub struct InvalidPositions<'a> {
+    nuc: &'a [u8],
+    // start of the chunk of 64 bytes covered by `mask`
+    pos: usize,
+    mask: u64,
+    chunk_mask: fn(&[u8]) -> u64,
+}
+
+impl<'a> InvalidPositions<'a> {
+    pub fn new(nuc: &'a [u8]) -> Self {
+        let mut chunk_mask: fn(&[u8]) -> u64 = mask_scalar;
+
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                chunk_mask = |chunk| unsafe { mask_avx(chunk) };
+            } else if is_x86_feature_detected!("ssse3") {
+                chunk_mask = |chunk| unsafe { mask_sse(chunk) };
+            }
+        }
+
+        InvalidPositions {
+            nuc,
+            pos: 0,
+            mask: chunk_mask(&nuc[..nuc.len().min(64)]),
+            chunk_mask,
+        }
+    }
+}
+
+impl<'a> Iterator for InvalidPositions<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.mask == 0 {
+            self.pos += 64;
+
+            if self.pos >= self.nuc.len() {
+                self.pos = self.nuc.len();
+                return None;
+            }
+
+            let end = (self.pos + 64).min(self.nuc.len());
+            self.mask = (self.chunk_mask)(&self.nuc[self.pos..end]);
+        }
+
+        let res = self.pos + self.mask.trailing_zeros() as usize;
+        self.mask &= self.mask - 1;
+
+        Some(res)
+    }
+}
+
 #[cfg(feature = "bench-internals")]
 pub fn pub_check_scalar(nuc: &[u8]) -> bool {
     check_scalar(nuc)
@@ -166,6 +386,23 @@ pub unsafe fn pub_check_sse(nuc: &[u8]) -> bool {
     check_sse(nuc)
 }
 
+#[cfg(feature = "bench-internals")]
+pub fn pub_first_invalid_scalar(nuc: &[u8]) -> Option<usize> {
+    first_invalid_scalar(nuc)
+}
+
+#[cfg(feature = "bench-internals")]
+#[target_feature(enable = "avx2")]
+pub unsafe fn pub_first_invalid_avx(nuc: &[u8]) -> Option<usize> {
+    first_invalid_avx(nuc)
+}
+
+#[cfg(feature = "bench-internals")]
+#[target_feature(enable = "ssse3")]
+pub unsafe fn pub_first_invalid_sse(nuc: &[u8]) -> Option<usize> {
+    first_invalid_sse(nuc)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -206,4 +443,75 @@ mod tests {
             }
         }
     }
+
+    const INVALID: &[u8] = b"ATCGATCGATNGATCGATCGATCGATCGATCGATCGATCGATCGATCG ATCGATCGATCG\xC1ATCGATCGATCGAT-CGA";
+
+    #[test]
+    fn test_first_invalid_scalar() {
+        assert_eq!(first_invalid_scalar(b"AUCGATCGATCGATCGATCGATCGATCGATCG"), None);
+        assert_eq!(first_invalid_scalar(&INVALID[12..]), Some(36));
+        assert_eq!(first_invalid_scalar(b""), None);
+    }
+
+    #[test]
+    fn test_first_invalid_avx() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                assert_eq!(unsafe { first_invalid_avx(b"AUCGATCGATCGATCGATCGATCGATCGATCG") }, None);
+                assert_eq!(unsafe { first_invalid_avx(&INVALID[12..]) }, Some(36));
+                assert_eq!(unsafe { first_invalid_avx(&INVALID[49..]) }, Some(12));
+                assert_eq!(unsafe { first_invalid_avx(&INVALID[62..]) }, Some(14));
+            }
+        }
+    }
+
+    #[test]
+    fn test_first_invalid_sse() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("ssse3") {
+                assert_eq!(unsafe { first_invalid_sse(b"AUCGATCGATCGATCGATCGATCGATCGATCG") }, None);
+                assert_eq!(unsafe { first_invalid_sse(&INVALID[12..]) }, Some(36));
+                assert_eq!(unsafe { first_invalid_sse(&INVALID[49..]) }, Some(12));
+                assert_eq!(unsafe { first_invalid_sse(&INVALID[62..]) }, Some(14));
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_mask_scalar() {
+        assert_eq!(invalid_mask_scalar(INVALID), vec![1 << 10 | 1 << 48 | 1 << 61, 1 << 12]);
+        assert_eq!(invalid_mask_scalar(b""), vec![]);
+    }
+
+    #[test]
+    fn test_invalid_mask_avx() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                assert_eq!(unsafe { invalid_mask_avx(INVALID) }, vec![1 << 10 | 1 << 48 | 1 << 61, 1 << 12]);
+                assert_eq!(unsafe { invalid_mask_avx(&INVALID[..64]) }, vec![1 << 10 | 1 << 48 | 1 << 61]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_mask_sse() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("ssse3") {
+                assert_eq!(unsafe { invalid_mask_sse(INVALID) }, vec![1 << 10 | 1 << 48 | 1 << 61, 1 << 12]);
+                assert_eq!(unsafe { invalid_mask_sse(&INVALID[..64]) }, vec![1 << 10 | 1 << 48 | 1 << 61]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_positions() {
+        assert_eq!(invalid_positions(INVALID).collect::<Vec<usize>>(), vec![10, 48, 61, 76]);
+        assert_eq!(invalid_positions(&INVALID[11..48]).next(), None);
+        assert_eq!(invalid_positions(b"").next(), None);
+        assert_eq!(invalid_positions(b"NNN").collect::<Vec<usize>>(), vec![0, 1, 2]);
+    }
 }
@@ -4,105 +4,325 @@ use std::arch::x86::*;
 #[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::*;
 
-use std::{alloc, ptr};
-
 pub fn complement(bits: &[u64]) -> Vec<u64> {
+    let mut res = Vec::with_capacity(bits.len());
+
+    unsafe {
+        complement_ptr(bits.as_ptr(), res.as_mut_ptr(), bits.len());
+        res.set_len(bits.len());
+    }
+
+    res
+}
+
+pub fn complement_in_place(bits: &mut [u64]) {
+    let ptr = bits.as_mut_ptr();
+
+    unsafe { complement_ptr(ptr, ptr, bits.len()) }
+}
+
+// `src` and `res_ptr` may be the same pointer, each word is read before being written
+unsafe fn complement_ptr(src: *const u64, res_ptr: *mut u64, len: usize) {
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     {
         if is_x86_feature_detected!("avx2") {
-            return unsafe { complement_avx(bits) };
+            return complement_avx(src, res_ptr, len);
         } else if is_x86_feature_detected!("sse2") {
-            return unsafe { complement_sse(bits) };
+            return complement_sse(src, res_ptr, len);
         }
     }
 
-    complement_scalar(bits)
+    complement_scalar(src, res_ptr, len)
 }
 
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 #[target_feature(enable = "avx2")]
-unsafe fn complement_avx(bits: &[u64]) -> Vec<u64> {
-    let ptr = bits.as_ptr() as *const __m256i;
-    let end_idx = bits.len() / 4;
-
-    let layout = alloc::Layout::from_size_align_unchecked(bits.len() * 8, 32);
-    let res_ptr = alloc::alloc(layout) as *mut __m256i;
+unsafe fn complement_avx(src: *const u64, res_ptr: *mut u64, len: usize) {
+    let ptr = src as *const __m256i;
+    let res = res_ptr as *mut __m256i;
+    let end_idx = len / 4;
 
     let mask = _mm256_set1_epi64x(0xAAAAAAAAAAAAAAAAu64 as i64);
 
     for i in 0..end_idx as isize {
         let v = _mm256_loadu_si256(ptr.offset(i));
         let v = _mm256_xor_si256(v, mask);
-        _mm256_store_si256(res_ptr.offset(i), v);
+        _mm256_storeu_si256(res.offset(i), v);
     }
 
-    if bits.len() % 4 > 0 {
+    if len % 4 > 0 {
         let end = end_idx * 4;
-
-        ptr::copy_nonoverlapping(
-            complement_scalar(&bits[end..]).as_ptr(),
-            res_ptr.offset(end_idx as isize) as *mut u64,
-            bits.len() - end,
-        );
+        complement_scalar(src.add(end), res_ptr.add(end), len - end);
     }
-
-    Vec::from_raw_parts(res_ptr as *mut u64, bits.len(), bits.len())
 }
 
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 #[target_feature(enable = "sse2")]
-unsafe fn complement_sse(bits: &[u64]) -> Vec<u64> {
-    let ptr = bits.as_ptr() as *const __m128i;
-    let end_idx = bits.len() / 2;
-
-    let layout = alloc::Layout::from_size_align_unchecked(bits.len() * 8, 16);
-    let res_ptr = alloc::alloc(layout) as *mut __m128i;
+unsafe fn complement_sse(src: *const u64, res_ptr: *mut u64, len: usize) {
+    let ptr = src as *const __m128i;
+    let res = res_ptr as *mut __m128i;
+    let end_idx = len / 2;
 
     let mask = _mm_set1_epi64x(0xAAAAAAAAAAAAAAAAu64 as i64);
 
     for i in 0..end_idx as isize {
         let v = _mm_loadu_si128(ptr.offset(i));
         let v = _mm_xor_si128(v, mask);
-        _mm_store_si128(res_ptr.offset(i), v);
+        _mm_storeu_si128(res.offset(i), v);
     }
 
-    if bits.len() % 2 > 0 {
-        *(res_ptr.offset(end_idx as isize) as *mut u64) =
-            *complement_scalar(&bits[(end_idx * 2)..]).get_unchecked(0);
+    if len % 2 > 0 {
+        let end = end_idx * 2;
+        complement_scalar(src.add(end), res_ptr.add(end), len - end);
     }
+}
 
-    Vec::from_raw_parts(res_ptr as *mut u64, bits.len(), bits.len())
+unsafe fn complement_scalar(src: *const u64, res_ptr: *mut u64, len: usize) {
+    for i in 0..len as isize {
+        // XOR 0b...10101010 to complement
+        *res_ptr.offset(i) = *src.offset(i) ^ 0xAAAAAAAAAAAAAAAAu64;
+    }
 }
 
-fn complement_scalar(bits: &[u64]) -> Vec<u64> {
-    unsafe {
-        let layout = alloc::Layout::from_size_align_unchecked(bits.len() * 8, 8);
-        let res_ptr = alloc::alloc(layout) as *mut u64;
+pub fn reverse_complement(bits: &[u64], len: usize) -> Vec<u64> {
+    if len > (bits.len() * 32) {
+        panic!(
+            "The length {} is greater than the number of nucleotides!",
+            len
+        );
+    }
 
-        for i in 0..bits.len() {
-            // XOR 0b...10101010 to complement
-            *res_ptr.offset(i as isize) = *bits.get_unchecked(i) ^ 0xAAAAAAAAAAAAAAAAu64;
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { reverse_complement_avx(bits, len) };
+        } else if is_x86_feature_detected!("ssse3") {
+            return unsafe { reverse_complement_sse(bits, len) };
         }
+    }
+
+    reverse_complement_scalar(bits, len)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn reverse_complement_avx(bits: &[u64], len: usize) -> Vec<u64> {
+    let words = (len / 32) + if len % 32 == 0 { 0 } else { 1 };
+    let end_idx = words / 4;
+    let leftover = words % 4;
+
+    let mut res: Vec<u64> = Vec::with_capacity(words);
+    let res_ptr = res.as_mut_ptr();
+    let ptr = bits.as_ptr();
+
+    // reverse the bytes of each 64-bit lane
+    let rev_bytes = _mm256_set_epi64x(
+        0x08090A0B0C0D0E0F,
+        0x0001020304050607,
+        0x08090A0B0C0D0E0F,
+        0x0001020304050607,
+    );
+    // lookup tables that reverse the two nucleotides of a nybble and complement them, the low
+    // nybble of each byte becomes the high nybble and vice versa
+    let lo_lut = _mm256_set_epi64x(
+        0x5010D0904000C080,
+        0x7030F0B06020E0A0,
+        0x5010D0904000C080,
+        0x7030F0B06020E0A0,
+    );
+    let hi_lut = _mm256_set_epi64x(
+        0x05010D0904000C08,
+        0x07030F0B06020E0A,
+        0x05010D0904000C08,
+        0x07030F0B06020E0A,
+    );
+    let mask = _mm256_set1_epi8(0x0F);
+
+    for i in 0..end_idx {
+        // the last words of the input are the first words of the output
+        let v = _mm256_loadu_si256(ptr.add(words - (i + 1) * 4) as *const __m256i);
+        let v = _mm256_shuffle_epi8(v, rev_bytes);
+
+        // reverse the nucleotides of each byte and complement them
+        let lo = _mm256_shuffle_epi8(lo_lut, _mm256_and_si256(v, mask));
+        let hi = _mm256_shuffle_epi8(hi_lut, _mm256_and_si256(_mm256_srli_epi16(v, 4), mask));
+        let v = _mm256_or_si256(lo, hi);
+
+        // reverse the order of the 64-bit lanes
+        let v = _mm256_permute4x64_epi64(v, 0b00011011);
+        _mm256_storeu_si256(res_ptr.add(i * 4) as *mut __m256i, v);
+    }
+
+    for i in 0..leftover {
+        *res_ptr.add(end_idx * 4 + i) = reverse_complement_word(*bits.get_unchecked(leftover - 1 - i));
+    }
+
+    res.set_len(words);
+
+    // the nucleotides after the end of the sequence are now at the start of the output
+    let shift = (words * 32 - len) * 2;
+
+    if shift > 0 {
+        let end_idx = (words - 1) / 4;
+        let lo_shift = _mm_cvtsi32_si128(shift as i32);
+        let hi_shift = _mm_cvtsi32_si128(64 - shift as i32);
+
+        for i in 0..end_idx {
+            let lo = _mm256_loadu_si256(res_ptr.add(i * 4) as *const __m256i);
+            let hi = _mm256_loadu_si256(res_ptr.add(i * 4 + 1) as *const __m256i);
+            let v = _mm256_or_si256(_mm256_srl_epi64(lo, lo_shift), _mm256_sll_epi64(hi, hi_shift));
+            _mm256_storeu_si256(res_ptr.add(i * 4) as *mut __m256i, v);
+        }
+
+        shift_scalar(&mut res[(end_idx * 4)..], shift);
+    }
+
+    res
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+unsafe fn reverse_complement_sse(bits: &[u64], len: usize) -> Vec<u64> {
+    let words = (len / 32) + if len % 32 == 0 { 0 } else { 1 };
+    let end_idx = words / 2;
+
+    let mut res: Vec<u64> = Vec::with_capacity(words);
+    let res_ptr = res.as_mut_ptr();
+    let ptr = bits.as_ptr();
+
+    // reverse the bytes of each 64-bit lane
+    let rev_bytes = _mm_set_epi64x(0x08090A0B0C0D0E0F, 0x0001020304050607);
+    // lookup tables that reverse the two nucleotides of a nybble and complement them, the low
+    // nybble of each byte becomes the high nybble and vice versa
+    let lo_lut = _mm_set_epi64x(0x5010D0904000C080, 0x7030F0B06020E0A0);
+    let hi_lut = _mm_set_epi64x(0x05010D0904000C08, 0x07030F0B06020E0A);
+    let mask = _mm_set1_epi8(0x0F);
+
+    for i in 0..end_idx {
+        // the last words of the input are the first words of the output
+        let v = _mm_loadu_si128(ptr.add(words - (i + 1) * 2) as *const __m128i);
+        let v = _mm_shuffle_epi8(v, rev_bytes);
+
+        // reverse the nucleotides of each byte and complement them
+        let lo = _mm_shuffle_epi8(lo_lut, _mm_and_si128(v, mask));
+        let hi = _mm_shuffle_epi8(hi_lut, _mm_and_si128(_mm_srli_epi16(v, 4), mask));
+        let v = _mm_or_si128(lo, hi);
+
+        // swap the two 64-bit lanes
+        let v = _mm_shuffle_epi32(v, 0b01001110);
+        _mm_storeu_si128(res_ptr.add(i * 2) as *mut __m128i, v);
+    }
+
+    if words % 2 > 0 {
+        *res_ptr.add(end_idx * 2) = reverse_complement_word(*bits.get_unchecked(0));
+    }
+
+    res.set_len(words);
+
+    // the nucleotides after the end of the sequence are now at the start of the output
+    let shift = (words * 32 - len) * 2;
+
+    if shift > 0 {
+        let end_idx = (words - 1) / 2;
+        let lo_shift = _mm_cvtsi32_si128(shift as i32);
+        let hi_shift = _mm_cvtsi32_si128(64 - shift as i32);
+
+        for i in 0..end_idx {
+            let lo = _mm_loadu_si128(res_ptr.add(i * 2) as *const __m128i);
+            let hi = _mm_loadu_si128(res_ptr.add(i * 2 + 1) as *const __m128i);
+            let v = _mm_or_si128(_mm_srl_epi64(lo, lo_shift), _mm_sll_epi64(hi, hi_shift));
+            _mm_storeu_si128(res_ptr.add(i * 2) as *mut __m128i, v);
+        }
+
+        shift_scalar(&mut res[(end_idx * 2)..], shift);
+    }
+
+    res
+}
+
+fn reverse_complement_scalar(bits: &[u64], len: usize) -> Vec<u64> {
+    let words = (len / 32) + if len % 32 == 0 { 0 } else { 1 };
+
+    let mut res = bits[..words]
+        .iter()
+        .rev()
+        .map(|&w| reverse_complement_word(w))
+        .collect::<Vec<u64>>();
 
-        Vec::from_raw_parts(res_ptr, bits.len(), bits.len())
+    // the nucleotides after the end of the sequence are now at the start of the output
+    let shift = (words * 32 - len) * 2;
+
+    if shift > 0 {
+        shift_scalar(&mut res, shift);
+    }
+
+    res
+}
+
+#[inline]
+fn reverse_complement_word(w: u64) -> u64 {
+    // reverse bytes, then nybbles in each byte, then nucleotides in each nybble
+    let w = w.swap_bytes();
+    let w = ((w >> 4) & 0x0F0F0F0F0F0F0F0Fu64) | ((w & 0x0F0F0F0F0F0F0F0Fu64) << 4);
+    let w = ((w >> 2) & 0x3333333333333333u64) | ((w & 0x3333333333333333u64) << 2);
+
+    // XOR 0b...10101010 to complement
+    w ^ 0xAAAAAAAAAAAAAAAAu64
+}
+
+// shift the words toward the start of the sequence by `shift` bits, with 0 < shift < 64
+fn shift_scalar(bits: &mut [u64], shift: usize) {
+    for i in 0..bits.len() {
+        let next = if i + 1 < bits.len() { bits[i + 1] << (64 - shift) } else { 0 };
+        bits[i] = (bits[i] >> shift) | next;
     }
 }
 
 #[cfg(feature = "bench-internals")]
 pub fn pub_complement_scalar(nuc: &[u64]) -> Vec<u64> {
-    complement_scalar(nuc)
+    let mut res = Vec::with_capacity(nuc.len());
+
+    unsafe {
+        complement_scalar(nuc.as_ptr(), res.as_mut_ptr(), nuc.len());
+        res.set_len(nuc.len());
+    }
+
+    res
 }
 
 #[cfg(feature = "bench-internals")]
 #[target_feature(enable = "avx2")]
 pub unsafe fn pub_complement_avx(nuc: &[u64]) -> Vec<u64> {
-    complement_avx(nuc)
+    let mut res = Vec::with_capacity(nuc.len());
+    complement_avx(nuc.as_ptr(), res.as_mut_ptr(), nuc.len());
+    res.set_len(nuc.len());
+    res
 }
 
 #[cfg(feature = "bench-internals")]
 #[target_feature(enable = "sse2")]
 pub unsafe fn pub_complement_sse(nuc: &[u64]) -> Vec<u64> {
-    complement_sse(nuc)
+    let mut res = Vec::with_capacity(nuc.len());
+    complement_sse(nuc.as_ptr(), res.as_mut_ptr(), nuc.len());
+    res.set_len(nuc.len());
+    res
+}
+
+#[cfg(feature = "bench-internals")]
+pub fn pub_reverse_complement_scalar(nuc: &[u64], len: usize) -> Vec<u64> {
+    reverse_complement_scalar(nuc, len)
+}
+
+#[cfg(feature = "bench-internals")]
+#[target_feature(enable = "avx2")]
+pub unsafe fn pub_reverse_complement_avx(nuc: &[u64], len: usize) -> Vec<u64> {
+    reverse_complement_avx(nuc, len)
+}
+
+#[cfg(feature = "bench-internals")]
+#[target_feature(enable = "ssse3")]
+pub unsafe fn pub_reverse_complement_sse(nuc: &[u64], len: usize) -> Vec<u64> {
+    reverse_complement_sse(nuc, len)
 }
 
 #[cfg(test)]
@@ -111,32 +331,59 @@ mod tests {
     use crate::nuc2bit::encode;
     use crate::utils::encoding_equals;
 
+    fn complement_with(f: unsafe fn(*const u64, *mut u64, usize), bits: &[u64]) -> Vec<u64> {
+        let mut res = bits.to_vec();
+        let ptr = res.as_mut_ptr();
+
+        unsafe { f(ptr, ptr, res.len()) };
+
+        res
+    }
+
     #[test]
     fn test_complement_scalar() {
         assert!(encoding_equals(
-            &complement_scalar(&encode(b"AUCGATCGATCGATCGATCGATCGATCGATCG")),
+            &complement_with(complement_scalar, &encode(b"AUCGATCGATCGATCGATCGATCGATCGATCG")),
             &encode(b"TAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGC"),
             32
         ));
         assert!(encoding_equals(
-            &complement_scalar(&encode(b"ATCG")),
+            &complement_with(complement_scalar, &encode(b"ATCG")),
             &encode(b"TAGC"),
             4
         ));
     }
 
+    #[test]
+    fn test_complement_in_place() {
+        let mut bits = encode(b"AUCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+        complement_in_place(&mut bits);
+
+        assert!(encoding_equals(
+            &bits,
+            &encode(b"TAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGC"),
+            148
+        ));
+
+        let mut bits = encode(b"ATCG");
+        complement_in_place(&mut bits);
+        complement_in_place(&mut bits);
+
+        assert!(encoding_equals(&bits, &encode(b"ATCG"), 4));
+    }
+
     #[test]
     fn test_complement_avx() {
         #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
         {
             if is_x86_feature_detected!("avx2") {
                 assert!(encoding_equals(
-                    &unsafe { complement_avx(&encode(b"AUCGATCGATCGATCGATCGATCGATCGATCG")) },
+                    &complement_with(complement_avx, &encode(b"AUCGATCGATCGATCGATCGATCGATCGATCG")),
                     &encode(b"TAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGC"),
                     32
                 ));
                 assert!(encoding_equals(
-                    &unsafe { complement_avx(&encode(b"ATCG")) },
+                    &complement_with(complement_avx, &encode(b"ATCG")),
                     &encode(b"TAGC"),
                     4
                 ));
@@ -150,16 +397,77 @@ mod tests {
         {
             if is_x86_feature_detected!("sse2") {
                 assert!(encoding_equals(
-                    &unsafe { complement_sse(&encode(b"AUCGATCGATCGATCGATCGATCGATCGATCG")) },
+                    &complement_with(complement_sse, &encode(b"AUCGATCGATCGATCGATCGATCGATCGATCG")),
                     &encode(b"TAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGC"),
                     32
                 ));
                 assert!(encoding_equals(
-                    &unsafe { complement_sse(&encode(b"ATCG")) },
+                    &complement_with(complement_sse, &encode(b"ATCG")),
                     &encode(b"TAGC"),
                     4
                 ));
             }
         }
     }
+
+    #[test]
+    fn test_reverse_complement_scalar() {
+        assert!(encoding_equals(
+            &reverse_complement_scalar(&encode(b"AACGATCGATCGATCGATCGATCGATCGATCG"), 32),
+            &encode(b"CGATCGATCGATCGATCGATCGATCGATCGTT"),
+            32
+        ));
+        assert!(encoding_equals(
+            &reverse_complement_scalar(&encode(b"AACGTTGCATCGATCGATCGATCGATCGATCGGTCA"), 36),
+            &encode(b"TGACCGATCGATCGATCGATCGATCGATGCAACGTT"),
+            36
+        ));
+        assert_eq!(reverse_complement_scalar(&encode(b"ATCGG"), 5), vec![0b1000110101]);
+    }
+
+    #[test]
+    fn test_reverse_complement_avx() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                let seq = b"AACGTTGCATCGATCGATCGATCGATCGATCGGTCAAACGTTGCATCGATCGATCGATCGATCGATCGGTCAAACGTTGCATCGATCGATCGATCGATCGATCGGTCAAACGTTGCATCGATCGATCGATCGATCGATCGGTCAAACGTTGCATCGATCGATCGATCGATCGATCGGTCA";
+                let rc = b"TGACCGATCGATCGATCGATCGATCGATGCAACGTTTGACCGATCGATCGATCGATCGATCGATGCAACGTTTGACCGATCGATCGATCGATCGATCGATGCAACGTTTGACCGATCGATCGATCGATCGATCGATGCAACGTTTGACCGATCGATCGATCGATCGATCGATGCAACGTT";
+
+                assert!(encoding_equals(
+                    &unsafe { reverse_complement_avx(&encode(seq), 180) },
+                    &encode(rc),
+                    180
+                ));
+                assert!(encoding_equals(
+                    &unsafe { reverse_complement_avx(&encode(&seq[..128]), 128) },
+                    &encode(&rc[52..]),
+                    128
+                ));
+                assert_eq!(unsafe { reverse_complement_avx(&encode(b"ATCGG"), 5) }, vec![0b1000110101]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_reverse_complement_sse() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("ssse3") {
+                let seq = b"AACGTTGCATCGATCGATCGATCGATCGATCGGTCAAACGTTGCATCGATCGATCGATCGATCGATCGGTCAAACGTTGCATCGATCGATCGATCGATCGATCGGTCAAACGTTGCATCGATCGATCGATCGATCGATCGGTCAAACGTTGCATCGATCGATCGATCGATCGATCGGTCA";
+                let rc = b"TGACCGATCGATCGATCGATCGATCGATGCAACGTTTGACCGATCGATCGATCGATCGATCGATGCAACGTTTGACCGATCGATCGATCGATCGATCGATGCAACGTTTGACCGATCGATCGATCGATCGATCGATGCAACGTTTGACCGATCGATCGATCGATCGATCGATGCAACGTT";
+
+                assert!(encoding_equals(
+                    &unsafe { reverse_complement_sse(&encode(seq), 180) },
+                    &encode(rc),
+                    180
+                ));
+                assert!(encoding_equals(
+                    &unsafe { reverse_complement_sse(&encode(&seq[..128]), 128) },
+                    &encode(&rc[52..]),
+                    128
+                ));
+                assert_eq!(unsafe { reverse_complement_sse(&encode(b"ATCGG"), 5) }, vec![0b1000110101]);
+            }
+        }
+    }
 }
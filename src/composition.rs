@@ -0,0 +1,306 @@
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+// counts of the bytes of an ASCII sequence, the nucleotides are counted in both cases so
+// `a + c + g + t + n + other` is the length and `lowercase` counts any lowercase letter
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Composition {
+    pub a: usize,
+    pub c: usize,
+    pub g: usize,
+    // T and U
+    pub t: usize,
+    pub n: usize,
+    pub lowercase: usize,
+    pub other: usize,
+}
+
+pub fn composition(nuc: &[u8]) -> Composition {
+    let counts = counts(nuc);
+
+    Composition {
+        a: counts[0],
+        c: counts[1],
+        g: counts[2],
+        t: counts[3],
+        n: counts[4],
+        lowercase: counts[5],
+        other: nuc.len() - counts[..5].iter().sum::<usize>(),
+    }
+}
+
+fn counts(nuc: &[u8]) -> [usize; 6] {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { counts_avx(nuc) };
+        } else if is_x86_feature_detected!("ssse3") {
+            return unsafe { counts_sse(nuc) };
+        }
+    }
+
+    counts_scalar(nuc)
+}
+
+// for each class, a bitset of its bytes for each 8-bit chunk of the bytes from 64 to 127, as the
+// lookup table of `check`
+const CLASSES: [i64; 6] = [
+    bitset(b"Aa"),
+    bitset(b"Cc"),
+    bitset(b"Gg"),
+    bitset(b"TtUu"),
+    bitset(b"Nn"),
+    bitset(b"abcdefghijklmnopqrstuvwxyz"),
+];
+
+const fn bitset(bytes: &[u8]) -> i64 {
+    let mut res = 0i64;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        res |= 1i64 << (bytes[i] - 64);
+        i += 1;
+    }
+
+    res
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn counts_avx(nuc: &[u8]) -> [usize; 6] {
+    let ptr = nuc.as_ptr() as *const __m256i;
+    let end_idx = nuc.len() / 32;
+
+    let mut luts = [_mm256_setzero_si256(); 6];
+
+    for (lut, &class) in luts.iter_mut().zip(CLASSES.iter()) {
+        *lut = _mm256_set_epi64x(class, 0, class, 0);
+    }
+
+    let shift_lut = _mm256_set1_epi64x(0x8040201008040201u64 as i64);
+    let mask = _mm256_set1_epi8(0b00001111);
+    let one = _mm256_set1_epi8(1);
+
+    union A {
+        v: __m256i,
+        a: [u64; 4],
+    }
+
+    let mut res = [0usize; 6];
+    let mut idx = 0;
+
+    while idx < end_idx {
+        // the 8-bit counters are summed before they overflow
+        let block_end = std::cmp::min(idx + 255, end_idx);
+        let mut acc = [_mm256_setzero_si256(); 6];
+
+        while idx < block_end {
+            let v = _mm256_loadu_si256(ptr.add(idx));
+
+            // use high 4 bits to lookup 8-bit chunk, bytes where MSB is 1 are in no class
+            let hi = _mm256_and_si256(_mm256_srli_epi16(v, 3), mask);
+            let lo_lut = _mm256_shuffle_epi8(shift_lut, v);
+
+            for k in 0..6 {
+                let class = _mm256_and_si256(lo_lut, _mm256_shuffle_epi8(luts[k], hi));
+                acc[k] = _mm256_add_epi8(acc[k], _mm256_min_epu8(class, one));
+            }
+
+            idx += 1;
+        }
+
+        for k in 0..6 {
+            let a = A {
+                v: _mm256_sad_epu8(acc[k], _mm256_setzero_si256()),
+            };
+            res[k] += ((a.a[0] + a.a[1]) + (a.a[2] + a.a[3])) as usize;
+        }
+    }
+
+    let tail = counts_scalar(&nuc[(end_idx * 32)..]);
+
+    for k in 0..6 {
+        res[k] += tail[k];
+    }
+
+    res
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+unsafe fn counts_sse(nuc: &[u8]) -> [usize; 6] {
+    let ptr = nuc.as_ptr() as *const __m128i;
+    let end_idx = nuc.len() / 16;
+
+    let mut luts = [_mm_setzero_si128(); 6];
+
+    for (lut, &class) in luts.iter_mut().zip(CLASSES.iter()) {
+        *lut = _mm_set_epi64x(class, 0);
+    }
+
+    let shift_lut = _mm_set1_epi64x(0x8040201008040201u64 as i64);
+    let mask = _mm_set1_epi8(0b00001111);
+    let one = _mm_set1_epi8(1);
+
+    union A {
+        v: __m128i,
+        a: [u64; 2],
+    }
+
+    let mut res = [0usize; 6];
+    let mut idx = 0;
+
+    while idx < end_idx {
+        let block_end = std::cmp::min(idx + 255, end_idx);
+        let mut acc = [_mm_setzero_si128(); 6];
+
+        while idx < block_end {
+            let v = _mm_loadu_si128(ptr.add(idx));
+
+            let hi = _mm_and_si128(_mm_srli_epi16(v, 3), mask);
+            let lo_lut = _mm_shuffle_epi8(shift_lut, v);
+
+            for k in 0..6 {
+                let class = _mm_and_si128(lo_lut, _mm_shuffle_epi8(luts[k], hi));
+                acc[k] = _mm_add_epi8(acc[k], _mm_min_epu8(class, one));
+            }
+
+            idx += 1;
+        }
+
+        for k in 0..6 {
+            let a = A {
+                v: _mm_sad_epu8(acc[k], _mm_setzero_si128()),
+            };
+            res[k] += (a.a[0] + a.a[1]) as usize;
+        }
+    }
+
+    let tail = counts_scalar(&nuc[(end_idx * 16)..]);
+
+    for k in 0..6 {
+        res[k] += tail[k];
+    }
+
+    res
+}
+
+// the class of each byte, 5 for the bytes that are not in any class
+static CLASS_LUT: [u8; 256] = {
+    let mut lut = [5u8; 256];
+    lut[b'A' as usize] = 0;
+    lut[b'a' as usize] = 0;
+    lut[b'C' as usize] = 1;
+    lut[b'c' as usize] = 1;
+    lut[b'G' as usize] = 2;
+    lut[b'g' as usize] = 2;
+    lut[b'T' as usize] = 3;
+    lut[b't' as usize] = 3;
+    lut[b'U' as usize] = 3;
+    lut[b'u' as usize] = 3;
+    lut[b'N' as usize] = 4;
+    lut[b'n' as usize] = 4;
+    lut
+};
+
+fn counts_scalar(nuc: &[u8]) -> [usize; 6] {
+    // the last counter is for the bytes in no class and is dropped
+    let mut res = [0usize; 7];
+
+    for &n in nuc {
+        res[CLASS_LUT[n as usize] as usize] += 1;
+        res[6] += n.is_ascii_lowercase() as usize;
+    }
+
+    [res[0], res[1], res[2], res[3], res[4], res[6]]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NUC: &[u8] = b"ACGTNacgtnUuXx-*\xC1\x80zZ ACGTTGCAACGTAAGGCCTTATATgcgcacgtacgtaggcTTAAACCCGGGTTTAGCTTGACCAGTCAGNNNNNnnnnAAAAAA";
+
+    fn naive(nuc: &[u8]) -> [usize; 6] {
+        let count = |bytes: &[u8]| nuc.iter().filter(|n| bytes.contains(n)).count();
+
+        [
+            count(b"Aa"),
+            count(b"Cc"),
+            count(b"Gg"),
+            count(b"TtUu"),
+            count(b"Nn"),
+            nuc.iter().filter(|n| n.is_ascii_lowercase()).count(),
+        ]
+    }
+
+    fn long() -> Vec<u8> {
+        (0..20000)
+            .map(|i| NUC[(i * 7 + i / 13) % NUC.len()])
+            .collect()
+    }
+
+    #[test]
+    fn test_counts_scalar() {
+        assert_eq!(counts_scalar(b"ACGTNacgtnUuXx-*\xC1zZ"), [2, 2, 2, 4, 2, 8]);
+        assert_eq!(counts_scalar(b""), [0; 6]);
+        assert_eq!(counts_scalar(NUC), naive(NUC));
+    }
+
+    #[test]
+    fn test_counts_avx() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                let long = long();
+
+                for n in 0..NUC.len() {
+                    assert_eq!(unsafe { counts_avx(&NUC[n..]) }, naive(&NUC[n..]));
+                }
+                assert_eq!(unsafe { counts_avx(&long) }, naive(&long));
+            }
+        }
+    }
+
+    #[test]
+    fn test_counts_sse() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("ssse3") {
+                let long = long();
+
+                for n in 0..NUC.len() {
+                    assert_eq!(unsafe { counts_sse(&NUC[n..]) }, naive(&NUC[n..]));
+                }
+                assert_eq!(unsafe { counts_sse(&long) }, naive(&long));
+            }
+        }
+    }
+
+    #[test]
+    fn test_composition() {
+        assert_eq!(
+            composition(b"ACGTNacgtnUuXx-*\xC1zZ"),
+            Composition {
+                a: 2,
+                c: 2,
+                g: 2,
+                t: 4,
+                n: 2,
+                lowercase: 8,
+                other: 7,
+            }
+        );
+        assert_eq!(composition(b""), Composition::default());
+
+        let long = long();
+        let res = composition(&long);
+        assert_eq!(
+            res.a + res.c + res.g + res.t + res.n + res.other,
+            long.len()
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use crate::bit2nuc::Codes;
+use crate::utils::compact_even_bits;
+
+// Levenshtein distance between the first `a_len` nucleotides of `a` and the first `b_len` of `b`,
+// with the bit-parallel algorithm of Myers extended to multiple words by Hyyrö
+pub fn edit_distance(a: &[u64], a_len: usize, b: &[u64], b_len: usize) -> usize {
+    distance(a, a_len, b, b_len, usize::MAX).unwrap()
+}
+
+// same as `edit_distance`, but `None` as soon as the distance is known to be greater than `max`
+pub fn edit_distance_bounded(
+    a: &[u64],
+    a_len: usize,
+    b: &[u64],
+    b_len: usize,
+    max: usize,
+) -> Option<usize> {
+    distance(a, a_len, b, b_len, max)
+}
+
+fn distance(a: &[u64], a_len: usize, b: &[u64], b_len: usize, max: usize) -> Option<usize> {
+    for &(bits, len) in &[(a, a_len), (b, b_len)] {
+        if len > (bits.len() * 32) {
+            panic!(
+                "The length {} is greater than the number of nucleotides!",
+                len
+            );
+        }
+    }
+
+    // the shorter sequence is the pattern, to have less words per column
+    let (pattern, m, text, n) = if a_len <= b_len {
+        (a, a_len, b, b_len)
+    } else {
+        (b, b_len, a, a_len)
+    };
+
+    if n - m > max {
+        return None;
+    }
+
+    if m == 0 {
+        return Some(n);
+    }
+
+    let blocks = (m / 64) + if m % 64 == 0 { 0 } else { 1 };
+    let last_bit = (m - 1) % 64;
+    let peq = build_peq(pattern, m);
+
+    let mut pv = vec![!0u64; blocks];
+    let mut mv = vec![0u64; blocks];
+    // score of the last row of each block, which starts as the distance to the empty text
+    let mut score = (0..blocks)
+        .map(|i| std::cmp::min((i + 1) * 64, m))
+        .collect::<Vec<_>>();
+
+    for (j, c) in Codes::new(text, n).enumerate() {
+        // the first row is the distance between the empty pattern and the text, so it increases
+        let mut h = 1;
+
+        for i in 0..blocks {
+            let bit = if i == blocks - 1 { last_bit } else { 63 };
+            h = advance_block(&mut pv[i], &mut mv[i], peq[i * 4 + c as usize], h, bit);
+            score[i] = (score[i] as isize + h as isize) as usize;
+        }
+
+        if max != usize::MAX {
+            // the distance can only grow along a path and every path crosses this column, each
+            // block is at least its last row minus the number of rows above it in the block
+            let lower = (0..blocks)
+                .map(|i| score[i].saturating_sub(if i == blocks - 1 { last_bit } else { 63 }))
+                .min()
+                .unwrap();
+
+            if std::cmp::min(lower, j + 1) > max {
+                return None;
+            }
+        }
+    }
+
+    let res = score[blocks - 1];
+
+    if res > max {
+        None
+    } else {
+        Some(res)
+    }
+}
+
+// one column of one block, `hin` and the result are the horizontal differences above the block
+// and at its row `bit`, as in edlib
+#[inline]
+fn advance_block(pv: &mut u64, mv: &mut u64, eq: u64, hin: i32, bit: usize) -> i32 {
+    let mut eq = eq;
+    let xv = eq | *mv;
+
+    if hin < 0 {
+        eq |= 1;
+    }
+
+    let xh = ((eq & *pv).wrapping_add(*pv) ^ *pv) | eq;
+    let mut ph = *mv | !(xh | *pv);
+    let mut mh = *pv & xh;
+
+    let hout = ((ph >> bit) & 1) as i32 - ((mh >> bit) & 1) as i32;
+
+    ph <<= 1;
+    mh <<= 1;
+
+    if hin < 0 {
+        mh |= 1;
+    } else if hin > 0 {
+        ph |= 1;
+    }
+
+    *pv = mh | !(xv | ph);
+    *mv = ph & xv;
+
+    hout
+}
+
+// for each block of 64 nucleotides of the pattern and each code, the bits of the nucleotides equal
+// to the code
+fn build_peq(pattern: &[u64], m: usize) -> Vec<u64> {
+    let blocks = (m / 64) + if m % 64 == 0 { 0 } else { 1 };
+    let mut peq = vec![0u64; blocks * 4];
+
+    for i in 0..blocks {
+        for c in 0..4u64 {
+            let mut eq = 0;
+
+            for half in 0..2 {
+                let idx = i * 2 + half;
+
+                if idx < pattern.len() {
+                    // the two bits of a nucleotide are zero if it is equal to `c`
+                    let x = pattern[idx] ^ (c * 0x5555555555555555);
+                    let equal = !(x | (x >> 1)) & 0x5555555555555555;
+
+                    eq |= compact_even_bits(equal) << (half * 32);
+                }
+            }
+
+            peq[i * 4 + c as usize] = eq;
+        }
+    }
+
+    // the padding after the end of the pattern is never equal
+    if m % 64 != 0 {
+        for eq in &mut peq[((blocks - 1) * 4)..] {
+            *eq &= (1 << (m % 64)) - 1;
+        }
+    }
+
+    peq
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nuc2bit::encode;
+    use crate::utils::random_nuc;
+
+    fn naive(a: &[u8], b: &[u8]) -> usize {
+        let mut prev = (0..=b.len()).collect::<Vec<_>>();
+
+        for i in 1..=a.len() {
+            let mut curr = vec![i; b.len() + 1];
+
+            for j in 1..=b.len() {
+                let sub = prev[j - 1] + if a[i - 1] == b[j - 1] { 0 } else { 1 };
+                curr[j] = sub.min(prev[j] + 1).min(curr[j - 1] + 1);
+            }
+
+            prev = curr;
+        }
+
+        prev[b.len()]
+    }
+
+    // a copy of `nuc` with some substitutions, insertions and deletions
+    fn mutate(nuc: &[u8], seed: u64) -> Vec<u8> {
+        let noise = random_nuc(nuc.len(), seed);
+        let mut res = Vec::new();
+
+        for (i, &n) in nuc.iter().enumerate() {
+            match (i as u64 + seed) % 13 {
+                0 => res.push(noise[i]),
+                1 => (),
+                2 => {
+                    res.push(n);
+                    res.push(noise[i]);
+                }
+                _ => res.push(n),
+            }
+        }
+
+        res
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance(&encode(b"ACGT"), 4, &encode(b"ACGT"), 4), 0);
+        assert_eq!(edit_distance(&encode(b"ACGT"), 4, &encode(b"AGT"), 3), 1);
+        assert_eq!(edit_distance(&encode(b"ACGT"), 4, &encode(b"TACG"), 4), 2);
+        assert_eq!(edit_distance(&encode(b"ACGT"), 4, &encode(b""), 0), 4);
+        assert_eq!(edit_distance(&encode(b""), 0, &encode(b"ACG"), 3), 3);
+        assert_eq!(edit_distance(&encode(b"AAAA"), 4, &encode(b"TTTTTT"), 6), 6);
+
+        for &(len, seed) in &[
+            (1, 1),
+            (31, 2),
+            (63, 3),
+            (64, 4),
+            (65, 5),
+            (128, 6),
+            (200, 7),
+            (300, 8),
+        ] {
+            let a = random_nuc(len, seed);
+            let b = mutate(&a, seed);
+            let c = random_nuc(len / 2 + 3, seed + 100);
+
+            for &(x, y) in &[(&a, &b), (&b, &a), (&a, &c), (&c, &b)] {
+                assert_eq!(
+                    edit_distance(&encode(x), x.len(), &encode(y), y.len()),
+                    naive(x, y)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_edit_distance_bounded() {
+        for &(len, seed) in &[(10, 1), (64, 2), (100, 3), (250, 4)] {
+            let a = random_nuc(len, seed);
+            let b = mutate(&a, seed);
+            let c = random_nuc(len, seed + 100);
+
+            for &(x, y) in &[(&a, &b), (&a, &c)] {
+                let d = naive(x, y);
+
+                for max in &[0, 1, d / 2, d.saturating_sub(1), d, d + 1, 1000] {
+                    let res = edit_distance_bounded(&encode(x), x.len(), &encode(y), y.len(), *max);
+                    assert_eq!(res, if d <= *max { Some(d) } else { None });
+                }
+            }
+        }
+    }
+}
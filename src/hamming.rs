@@ -4,6 +4,12 @@ use std::arch::x86::*;
 #[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::*;
 
+use crate::bit2nuc::Codes;
+use crate::mismatch::mismatch_mask;
+use crate::utils::word_at;
+
+use std::ops::Range;
+
 pub fn hamming(a: &[u64], b: &[u64], len: usize) -> usize {
     if len / 64 <= 8 {
         return hamming_scalar(a, b, len);
@@ -27,10 +33,27 @@ unsafe fn hamming_avx(a: &[u64], b: &[u64], len: usize) -> usize {
     let end_idx = (((len / 32) / 8) / 2) / 4;
 
     let mut res = _mm256_setzero_si256();
-    let mut acc = [_mm256_setzero_si256(); 2];
     let a_ptr = a.as_ptr() as *const __m256i;
     let b_ptr = b.as_ptr() as *const __m256i;
 
+    for i in 0..end_idx {
+        res = _mm256_add_epi64(res, hamming_block_avx(a_ptr.add(i * 16), b_ptr.add(i * 16)));
+    }
+
+    let res = sum_avx(res);
+
+    let end = end_idx * 8 * 2 * 4;
+
+    res as usize + hamming_scalar_fast(&a[end..], &b[end..], len - (end * 32))
+}
+
+// hamming distance of the 16 vectors (2048 nucleotides) at `a_ptr` and `b_ptr`, as 4 partial sums
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn hamming_block_avx(a_ptr: *const __m256i, b_ptr: *const __m256i) -> __m256i {
+    let mut acc = [_mm256_setzero_si256(); 2];
+
     // bit patterns:
     // 0b1111, 0b1110, 0b1101, 0b1100, 0b1011, 0b1010, 0b1001, 0b1000, 0b0111, 0b0110, 0b0101,
     // 0b0100, 0b0011, 0b0010, 0b0001, 0b0000
@@ -38,48 +61,44 @@ unsafe fn hamming_avx(a: &[u64], b: &[u64], len: usize) -> usize {
     let lut = _mm256_set_epi64x(0x0202020102020201, 0x0202020101010100, 0x0202020102020201, 0x0202020101010100);
     let mask = _mm256_set1_epi8(0x0F);
 
-    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-    #[target_feature(enable = "avx2")]
-    #[inline]
-    unsafe fn internal_hamming(lut: __m256i, mask: __m256i, a: __m256i, b: __m256i) -> __m256i {
-        let xor = _mm256_xor_si256(a, b);
-        let lo_nybbles_lut = _mm256_shuffle_epi8(lut, _mm256_and_si256(xor, mask));
-        let hi_nybbles_lut = _mm256_shuffle_epi8(lut, _mm256_and_si256(_mm256_srli_epi16(xor, 4), mask));
-        _mm256_add_epi8(lo_nybbles_lut, hi_nybbles_lut)
-    }
-
     let mut idx = 0;
 
-    for _i in 0..end_idx {
-        for _j in 0..8 {
-            let d = internal_hamming(lut, mask, _mm256_loadu_si256(a_ptr.offset(idx + 0)), _mm256_loadu_si256(b_ptr.offset(idx + 0)));
-            acc[0] = _mm256_add_epi8(acc[0], d);
+    for _j in 0..8 {
+        let d = internal_hamming_avx(lut, mask, _mm256_loadu_si256(a_ptr.offset(idx + 0)), _mm256_loadu_si256(b_ptr.offset(idx + 0)));
+        acc[0] = _mm256_add_epi8(acc[0], d);
 
-            let d = internal_hamming(lut, mask, _mm256_loadu_si256(a_ptr.offset(idx + 1)), _mm256_loadu_si256(b_ptr.offset(idx + 1)));
-            acc[1] = _mm256_add_epi8(acc[1], d);
+        let d = internal_hamming_avx(lut, mask, _mm256_loadu_si256(a_ptr.offset(idx + 1)), _mm256_loadu_si256(b_ptr.offset(idx + 1)));
+        acc[1] = _mm256_add_epi8(acc[1], d);
 
-            idx += 2;
-        }
+        idx += 2;
+    }
 
-        let sum = _mm256_add_epi8(acc[0], acc[1]);
-        let sad = _mm256_sad_epu8(sum, _mm256_setzero_si256());
-        res = _mm256_add_epi64(res, sad);
+    let sum = _mm256_add_epi8(acc[0], acc[1]);
+    _mm256_sad_epu8(sum, _mm256_setzero_si256())
+}
 
-        acc[0] = _mm256_setzero_si256();
-        acc[1] = _mm256_setzero_si256();
-    }
+// the hamming distance of each byte of `a` and `b`
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn internal_hamming_avx(lut: __m256i, mask: __m256i, a: __m256i, b: __m256i) -> __m256i {
+    let xor = _mm256_xor_si256(a, b);
+    let lo_nybbles_lut = _mm256_shuffle_epi8(lut, _mm256_and_si256(xor, mask));
+    let hi_nybbles_lut = _mm256_shuffle_epi8(lut, _mm256_and_si256(_mm256_srli_epi16(xor, 4), mask));
+    _mm256_add_epi8(lo_nybbles_lut, hi_nybbles_lut)
+}
 
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn sum_avx(v: __m256i) -> u64 {
     union A {
         v: __m256i,
         a: [u64; 4]
     }
 
-    let arr = A { v: res };
-    let res = (arr.a[0] + arr.a[1]) + (arr.a[2] + arr.a[3]);
-
-    let end = end_idx * 8 * 2 * 4;
-
-    res as usize + hamming_scalar_fast(&a[end..], &b[end..], len - (end * 32))
+    let arr = A { v };
+    (arr.a[0] + arr.a[1]) + (arr.a[2] + arr.a[3])
 }
 
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
@@ -88,55 +107,67 @@ unsafe fn hamming_sse(a: &[u64], b: &[u64], len: usize) -> usize {
     let end_idx = (((len / 32) / 8) / 2) / 2;
 
     let mut res = _mm_setzero_si128();
-    let mut acc = [_mm_setzero_si128(); 2];
     let a_ptr = a.as_ptr() as *const __m128i;
     let b_ptr = b.as_ptr() as *const __m128i;
 
+    for i in 0..end_idx {
+        res = _mm_add_epi64(res, hamming_block_sse(a_ptr.add(i * 16), b_ptr.add(i * 16)));
+    }
+
+    let res = sum_sse(res);
+
+    let end = end_idx * 8 * 2 * 2;
+
+    res as usize + hamming_scalar_fast(&a[end..], &b[end..], len - (end * 32))
+}
+
+// hamming distance of the 16 vectors (1024 nucleotides) at `a_ptr` and `b_ptr`, as 2 partial sums
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+#[inline]
+unsafe fn hamming_block_sse(a_ptr: *const __m128i, b_ptr: *const __m128i) -> __m128i {
+    let mut acc = [_mm_setzero_si128(); 2];
+
     let lut = _mm_set_epi64x(0x0202020102020201, 0x0202020101010100);
     let mask = _mm_set1_epi8(0x0F);
 
-    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-    #[target_feature(enable = "ssse3")]
-    #[inline]
-    unsafe fn internal_hamming(lut: __m128i, mask: __m128i, a: __m128i, b: __m128i) -> __m128i {
-        let xor = _mm_xor_si128(a, b);
-        let lo_nybbles_lut = _mm_shuffle_epi8(lut, _mm_and_si128(xor, mask));
-        let hi_nybbles_lut = _mm_shuffle_epi8(lut, _mm_and_si128(_mm_srli_epi16(xor, 4), mask));
-        _mm_add_epi8(lo_nybbles_lut, hi_nybbles_lut)
-    }
-
     let mut idx = 0;
 
-    for _i in 0..end_idx {
-        for _j in 0..8 {
-            let d = internal_hamming(lut, mask, _mm_loadu_si128(a_ptr.offset(idx + 0)), _mm_loadu_si128(b_ptr.offset(idx + 0)));
-            acc[0] = _mm_add_epi8(acc[0], d);
+    for _j in 0..8 {
+        let d = internal_hamming_sse(lut, mask, _mm_loadu_si128(a_ptr.offset(idx + 0)), _mm_loadu_si128(b_ptr.offset(idx + 0)));
+        acc[0] = _mm_add_epi8(acc[0], d);
 
-            let d = internal_hamming(lut, mask, _mm_loadu_si128(a_ptr.offset(idx + 1)), _mm_loadu_si128(b_ptr.offset(idx + 1)));
-            acc[1] = _mm_add_epi8(acc[1], d);
+        let d = internal_hamming_sse(lut, mask, _mm_loadu_si128(a_ptr.offset(idx + 1)), _mm_loadu_si128(b_ptr.offset(idx + 1)));
+        acc[1] = _mm_add_epi8(acc[1], d);
 
-            idx += 2;
-        }
+        idx += 2;
+    }
 
-        let sum = _mm_add_epi8(acc[0], acc[1]);
-        let sad = _mm_sad_epu8(sum, _mm_setzero_si128());
-        res = _mm_add_epi64(res, sad);
+    let sum = _mm_add_epi8(acc[0], acc[1]);
+    _mm_sad_epu8(sum, _mm_setzero_si128())
+}
 
-        acc[0] = _mm_setzero_si128();
-        acc[1] = _mm_setzero_si128();
-    }
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+#[inline]
+unsafe fn internal_hamming_sse(lut: __m128i, mask: __m128i, a: __m128i, b: __m128i) -> __m128i {
+    let xor = _mm_xor_si128(a, b);
+    let lo_nybbles_lut = _mm_shuffle_epi8(lut, _mm_and_si128(xor, mask));
+    let hi_nybbles_lut = _mm_shuffle_epi8(lut, _mm_and_si128(_mm_srli_epi16(xor, 4), mask));
+    _mm_add_epi8(lo_nybbles_lut, hi_nybbles_lut)
+}
 
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+#[inline]
+unsafe fn sum_sse(v: __m128i) -> u64 {
     union A {
         v: __m128i,
         a: [u64; 2]
     }
 
-    let arr = A { v: res };
-    let res = arr.a[0] + arr.a[1];
-
-    let end = end_idx * 8 * 2 * 2;
-
-    res as usize + hamming_scalar_fast(&a[end..], &b[end..], len - (end * 32))
+    let arr = A { v };
+    arr.a[0] + arr.a[1]
 }
 
 // likely faster than hamming_scalar for long sequences
@@ -202,36 +233,1275 @@ fn hamming_scalar(a: &[u64], b: &[u64], len: usize) -> usize {
     res
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+// the mismatches split into transitions (A <-> G and C <-> T, the two bits differ) and
+// transversions (one bit differs), as `(transitions, transversions)`
+pub fn hamming_ts_tv(a: &[u64], b: &[u64], len: usize) -> (usize, usize) {
+    if len / 64 <= 8 {
+        return hamming_ts_tv_scalar(a, b, len);
+    }
 
-    #[test]
-    fn test_hamming_scalar() {
-        assert_eq!(hamming_scalar(&vec![0x0101010101010101; 128], &vec![0x0101010101010100; 128], 4096), 128);
-        assert_eq!(hamming_scalar_fast(&vec![0x0101010101010101; 128], &vec![0x0101010101010100; 128], 4096), 128);
-        assert_eq!(hamming_scalar(&vec![0b010101], &vec![0b010100], 3), 1);
-        assert_eq!(hamming_scalar_fast(&vec![0b010101], &vec![0b010100], 3), 1);
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { hamming_ts_tv_avx(a, b, len) };
+        } else if is_x86_feature_detected!("ssse3") {
+            return unsafe { hamming_ts_tv_sse(a, b, len) };
+        }
     }
 
-    #[test]
-    fn test_hamming_avx() {
-        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-        {
-            if is_x86_feature_detected!("avx2") {
-                assert_eq!(unsafe { hamming_avx(&vec![0x0101010101010101; 128], &vec![0x0101010101010100; 128], 4096) }, 128);
-                assert_eq!(unsafe { hamming_avx(&vec![0b010101], &vec![0b010100], 3) }, 1);
-            }
+    hamming_ts_tv_scalar(a, b, len)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn hamming_ts_tv_avx(a: &[u64], b: &[u64], len: usize) -> (usize, usize) {
+    let end_idx = (((len / 32) / 8) / 2) / 4;
+
+    let mut res = [_mm256_setzero_si256(); 2];
+    let mut acc = [_mm256_setzero_si256(); 2];
+    let a_ptr = a.as_ptr() as *const __m256i;
+    let b_ptr = b.as_ptr() as *const __m256i;
+
+    // for each bit pattern of two nucleotides, the number of 0b11 (transitions) and of 0b01 or
+    // 0b10 (transversions)
+    let ts_lut = _mm256_set_epi64x(0x0201010101000000, 0x0100000001000000, 0x0201010101000000, 0x0100000001000000);
+    let tv_lut = _mm256_set_epi64x(0x0001010001020201, 0x0102020100010100, 0x0001010001020201, 0x0102020100010100);
+    let mask = _mm256_set1_epi8(0x0F);
+
+    let mut idx = 0;
+
+    for _i in 0..end_idx {
+        for _j in 0..16 {
+            let xor = _mm256_xor_si256(_mm256_loadu_si256(a_ptr.offset(idx)), _mm256_loadu_si256(b_ptr.offset(idx)));
+            let lo = _mm256_and_si256(xor, mask);
+            let hi = _mm256_and_si256(_mm256_srli_epi16(xor, 4), mask);
+
+            acc[0] = _mm256_add_epi8(acc[0], _mm256_add_epi8(_mm256_shuffle_epi8(ts_lut, lo), _mm256_shuffle_epi8(ts_lut, hi)));
+            acc[1] = _mm256_add_epi8(acc[1], _mm256_add_epi8(_mm256_shuffle_epi8(tv_lut, lo), _mm256_shuffle_epi8(tv_lut, hi)));
+
+            idx += 1;
         }
+
+        res[0] = _mm256_add_epi64(res[0], _mm256_sad_epu8(acc[0], _mm256_setzero_si256()));
+        res[1] = _mm256_add_epi64(res[1], _mm256_sad_epu8(acc[1], _mm256_setzero_si256()));
+
+        acc[0] = _mm256_setzero_si256();
+        acc[1] = _mm256_setzero_si256();
     }
 
-    #[test]
-    fn test_hamming_sse() {
-        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-        {
-            if is_x86_feature_detected!("ssse3") {
-                assert_eq!(unsafe { hamming_sse(&vec![0x0101010101010101; 128], &vec![0x0101010101010100; 128], 4096) }, 128);
-                assert_eq!(unsafe { hamming_sse(&vec![0b010101], &vec![0b010100], 3) }, 1);
+    union A {
+        v: __m256i,
+        a: [u64; 4]
+    }
+
+    let ts = A { v: res[0] };
+    let tv = A { v: res[1] };
+
+    let end = end_idx * 8 * 2 * 4;
+    let (ts_tail, tv_tail) = hamming_ts_tv_scalar(&a[end..], &b[end..], len - (end * 32));
+
+    (
+        ((ts.a[0] + ts.a[1]) + (ts.a[2] + ts.a[3])) as usize + ts_tail,
+        ((tv.a[0] + tv.a[1]) + (tv.a[2] + tv.a[3])) as usize + tv_tail,
+    )
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+unsafe fn hamming_ts_tv_sse(a: &[u64], b: &[u64], len: usize) -> (usize, usize) {
+    let end_idx = (((len / 32) / 8) / 2) / 2;
+
+    let mut res = [_mm_setzero_si128(); 2];
+    let mut acc = [_mm_setzero_si128(); 2];
+    let a_ptr = a.as_ptr() as *const __m128i;
+    let b_ptr = b.as_ptr() as *const __m128i;
+
+    let ts_lut = _mm_set_epi64x(0x0201010101000000, 0x0100000001000000);
+    let tv_lut = _mm_set_epi64x(0x0001010001020201, 0x0102020100010100);
+    let mask = _mm_set1_epi8(0x0F);
+
+    let mut idx = 0;
+
+    for _i in 0..end_idx {
+        for _j in 0..16 {
+            let xor = _mm_xor_si128(_mm_loadu_si128(a_ptr.offset(idx)), _mm_loadu_si128(b_ptr.offset(idx)));
+            let lo = _mm_and_si128(xor, mask);
+            let hi = _mm_and_si128(_mm_srli_epi16(xor, 4), mask);
+
+            acc[0] = _mm_add_epi8(acc[0], _mm_add_epi8(_mm_shuffle_epi8(ts_lut, lo), _mm_shuffle_epi8(ts_lut, hi)));
+            acc[1] = _mm_add_epi8(acc[1], _mm_add_epi8(_mm_shuffle_epi8(tv_lut, lo), _mm_shuffle_epi8(tv_lut, hi)));
+
+            idx += 1;
+        }
+
+        res[0] = _mm_add_epi64(res[0], _mm_sad_epu8(acc[0], _mm_setzero_si128()));
+        res[1] = _mm_add_epi64(res[1], _mm_sad_epu8(acc[1], _mm_setzero_si128()));
+
+        acc[0] = _mm_setzero_si128();
+        acc[1] = _mm_setzero_si128();
+    }
+
+    union A {
+        v: __m128i,
+        a: [u64; 2]
+    }
+
+    let ts = A { v: res[0] };
+    let tv = A { v: res[1] };
+
+    let end = end_idx * 8 * 2 * 2;
+    let (ts_tail, tv_tail) = hamming_ts_tv_scalar(&a[end..], &b[end..], len - (end * 32));
+
+    ((ts.a[0] + ts.a[1]) as usize + ts_tail, (tv.a[0] + tv.a[1]) as usize + tv_tail)
+}
+
+fn hamming_ts_tv_scalar(a: &[u64], b: &[u64], len: usize) -> (usize, usize) {
+    let mut ts = 0;
+    let mut tv = 0;
+    let end_idx = len / 32;
+
+    let mask = 0x5555555555555555u64; // 0b...01010101
+
+    for i in 0..end_idx {
+        let xor = unsafe { *a.get_unchecked(i) ^ *b.get_unchecked(i) };
+        ts += ((xor >> 1) & xor & mask).count_ones() as usize;
+        tv += (((xor >> 1) ^ xor) & mask).count_ones() as usize;
+    }
+
+    let leftover = (len % 32) * 2;
+
+    if leftover > 0 {
+        let xor = unsafe { (*a.get_unchecked(end_idx) ^ *b.get_unchecked(end_idx)) & ((1 << leftover) - 1) };
+        ts += ((xor >> 1) & xor & mask).count_ones() as usize;
+        tv += (((xor >> 1) ^ xor) & mask).count_ones() as usize;
+    }
+
+    (ts, tv)
+}
+
+// same as `hamming`, but stops with `None` as soon as the distance is greater than `max`
+pub fn hamming_bounded(a: &[u64], b: &[u64], len: usize, max: usize) -> Option<usize> {
+    if len / 64 <= 8 {
+        return hamming_scalar_bounded(a, b, len, max);
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { hamming_bounded_avx(a, b, len, max) };
+        } else if is_x86_feature_detected!("ssse3") {
+            return unsafe { hamming_bounded_sse(a, b, len, max) };
+        }
+    }
+
+    hamming_scalar_bounded(a, b, len, max)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn hamming_bounded_avx(a: &[u64], b: &[u64], len: usize, max: usize) -> Option<usize> {
+    let end_idx = (((len / 32) / 8) / 2) / 4;
+
+    let mut res = 0;
+    let a_ptr = a.as_ptr() as *const __m256i;
+    let b_ptr = b.as_ptr() as *const __m256i;
+
+    for i in 0..end_idx {
+        // the distance is checked every 2048 nucleotides
+        res += sum_avx(hamming_block_avx(a_ptr.add(i * 16), b_ptr.add(i * 16))) as usize;
+
+        if res > max {
+            return None;
+        }
+    }
+
+    let end = end_idx * 8 * 2 * 4;
+
+    hamming_scalar_bounded(&a[end..], &b[end..], len - (end * 32), max - res).map(|d| d + res)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+unsafe fn hamming_bounded_sse(a: &[u64], b: &[u64], len: usize, max: usize) -> Option<usize> {
+    let end_idx = (((len / 32) / 8) / 2) / 2;
+
+    let mut res = 0;
+    let a_ptr = a.as_ptr() as *const __m128i;
+    let b_ptr = b.as_ptr() as *const __m128i;
+
+    for i in 0..end_idx {
+        // the distance is checked every 1024 nucleotides
+        res += sum_sse(hamming_block_sse(a_ptr.add(i * 16), b_ptr.add(i * 16))) as usize;
+
+        if res > max {
+            return None;
+        }
+    }
+
+    let end = end_idx * 8 * 2 * 2;
+
+    hamming_scalar_bounded(&a[end..], &b[end..], len - (end * 32), max - res).map(|d| d + res)
+}
+
+fn hamming_scalar_bounded(a: &[u64], b: &[u64], len: usize, max: usize) -> Option<usize> {
+    let mut res = 0;
+    let end_idx = len / 32;
+
+    let mask = 0x5555555555555555u64; // 0b...01010101
+
+    for i in 0..end_idx {
+        let xor = unsafe { *a.get_unchecked(i) ^ *b.get_unchecked(i) };
+        res += (((xor >> 1) | xor) & mask).count_ones() as usize;
+
+        if res > max {
+            return None;
+        }
+    }
+
+    let leftover = (len % 32) * 2;
+
+    if leftover > 0 {
+        let xor = unsafe { (*a.get_unchecked(end_idx) ^ *b.get_unchecked(end_idx)) & ((1 << leftover) - 1) };
+        res += (((xor >> 1) | xor) & mask).count_ones() as usize;
+    }
+
+    if res > max {
+        None
+    } else {
+        Some(res)
+    }
+}
+
+// hamming distance between the nucleotides `a_offset..(a_offset + len)` of `a` and
+// `b_offset..(b_offset + len)` of `b`
+pub fn hamming_at(a: &[u64], a_offset: usize, b: &[u64], b_offset: usize, len: usize) -> usize {
+    for &(bits, offset) in &[(a, a_offset), (b, b_offset)] {
+        if offset + len > (bits.len() * 32) {
+            panic!(
+                "The range {}..{} is out of the {} nucleotides!",
+                offset,
+                offset + len,
+                bits.len() * 32
+            );
+        }
+    }
+
+    if len / 64 <= 8 {
+        return hamming_shifted_scalar(a, a_offset, b, b_offset, len);
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { hamming_at_avx(a, a_offset, b, b_offset, len) };
+        } else if is_x86_feature_detected!("ssse3") {
+            return unsafe { hamming_at_sse(a, a_offset, b, b_offset, len) };
+        }
+    }
+
+    hamming_shifted_scalar(a, a_offset, b, b_offset, len)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn hamming_at_avx(a: &[u64], a_offset: usize, b: &[u64], b_offset: usize, len: usize) -> usize {
+    let (a_idx, b_idx) = (a_offset / 32, b_offset / 32);
+
+    // the next word of each lane is loaded too, so the last vector must not reach the last word
+    let end_idx = (len / 32).min(a.len() - a_idx - 1).min(b.len() - b_idx - 1) / 4;
+
+    let mut res = _mm256_setzero_si256();
+    let a_ptr = a.as_ptr().offset(a_idx as isize);
+    let b_ptr = b.as_ptr().offset(b_idx as isize);
+
+    // a shift by 64 gives zeros, so aligned offsets do not need a special case
+    let a_shift = _mm_set_epi64x(0, ((a_offset % 32) * 2) as i64);
+    let a_shift_next = _mm_set_epi64x(0, (64 - (a_offset % 32) * 2) as i64);
+    let b_shift = _mm_set_epi64x(0, ((b_offset % 32) * 2) as i64);
+    let b_shift_next = _mm_set_epi64x(0, (64 - (b_offset % 32) * 2) as i64);
+
+    let lut = _mm256_set_epi64x(0x0202020102020201, 0x0202020101010100, 0x0202020102020201, 0x0202020101010100);
+    let mask = _mm256_set1_epi8(0x0F);
+
+    for i in 0..end_idx as isize {
+        let a_curr = _mm256_loadu_si256(a_ptr.offset(i * 4) as *const __m256i);
+        let a_next = _mm256_loadu_si256(a_ptr.offset(i * 4 + 1) as *const __m256i);
+        let b_curr = _mm256_loadu_si256(b_ptr.offset(i * 4) as *const __m256i);
+        let b_next = _mm256_loadu_si256(b_ptr.offset(i * 4 + 1) as *const __m256i);
+
+        // realign both sequences on the start of their range
+        let a_v = _mm256_or_si256(_mm256_srl_epi64(a_curr, a_shift), _mm256_sll_epi64(a_next, a_shift_next));
+        let b_v = _mm256_or_si256(_mm256_srl_epi64(b_curr, b_shift), _mm256_sll_epi64(b_next, b_shift_next));
+
+        let d = internal_hamming_avx(lut, mask, a_v, b_v);
+
+        res = _mm256_add_epi64(res, _mm256_sad_epu8(d, _mm256_setzero_si256()));
+    }
+
+    let res = sum_avx(res);
+
+    let end = end_idx * 4 * 32;
+
+    res as usize + hamming_shifted_scalar(a, a_offset + end, b, b_offset + end, len - end)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+unsafe fn hamming_at_sse(a: &[u64], a_offset: usize, b: &[u64], b_offset: usize, len: usize) -> usize {
+    let (a_idx, b_idx) = (a_offset / 32, b_offset / 32);
+
+    // the next word of each lane is loaded too, so the last vector must not reach the last word
+    let end_idx = (len / 32).min(a.len() - a_idx - 1).min(b.len() - b_idx - 1) / 2;
+
+    let mut res = _mm_setzero_si128();
+    let a_ptr = a.as_ptr().offset(a_idx as isize);
+    let b_ptr = b.as_ptr().offset(b_idx as isize);
+
+    // a shift by 64 gives zeros, so aligned offsets do not need a special case
+    let a_shift = _mm_set_epi64x(0, ((a_offset % 32) * 2) as i64);
+    let a_shift_next = _mm_set_epi64x(0, (64 - (a_offset % 32) * 2) as i64);
+    let b_shift = _mm_set_epi64x(0, ((b_offset % 32) * 2) as i64);
+    let b_shift_next = _mm_set_epi64x(0, (64 - (b_offset % 32) * 2) as i64);
+
+    let lut = _mm_set_epi64x(0x0202020102020201, 0x0202020101010100);
+    let mask = _mm_set1_epi8(0x0F);
+
+    for i in 0..end_idx as isize {
+        let a_curr = _mm_loadu_si128(a_ptr.offset(i * 2) as *const __m128i);
+        let a_next = _mm_loadu_si128(a_ptr.offset(i * 2 + 1) as *const __m128i);
+        let b_curr = _mm_loadu_si128(b_ptr.offset(i * 2) as *const __m128i);
+        let b_next = _mm_loadu_si128(b_ptr.offset(i * 2 + 1) as *const __m128i);
+
+        // realign both sequences on the start of their range
+        let a_v = _mm_or_si128(_mm_srl_epi64(a_curr, a_shift), _mm_sll_epi64(a_next, a_shift_next));
+        let b_v = _mm_or_si128(_mm_srl_epi64(b_curr, b_shift), _mm_sll_epi64(b_next, b_shift_next));
+
+        let d = internal_hamming_sse(lut, mask, a_v, b_v);
+
+        res = _mm_add_epi64(res, _mm_sad_epu8(d, _mm_setzero_si128()));
+    }
+
+    let res = sum_sse(res);
+
+    let end = end_idx * 2 * 32;
+
+    res as usize + hamming_shifted_scalar(a, a_offset + end, b, b_offset + end, len - end)
+}
+
+// hamming distance between `a[a_offset..a_offset + len]` and `b[b_offset..b_offset + len]`,
+// the nucleotides of `b` are realigned on those of `a` one word at a time
+fn hamming_shifted_scalar(
+    a: &[u64],
+    a_offset: usize,
+    b: &[u64],
+    b_offset: usize,
+    len: usize,
+) -> usize {
+    let mut res = 0;
+    let end_idx = len / 32;
+
+    let mask = 0x5555555555555555u64; // 0b...01010101
+
+    for i in 0..end_idx {
+        let xor = word_at(a, a_offset + i * 32) ^ word_at(b, b_offset + i * 32);
+        res += (((xor >> 1) | xor) & mask).count_ones() as usize;
+    }
+
+    let leftover = (len % 32) * 2;
+
+    if leftover > 0 {
+        let end = end_idx * 32;
+        let xor = (word_at(a, a_offset + end) ^ word_at(b, b_offset + end)) & ((1 << leftover) - 1);
+        res += (((xor >> 1) | xor) & mask).count_ones() as usize;
+    }
+
+    res
+}
+
+// hamming distance between the first `m` nucleotides of `pattern` and the `m` nucleotides of
+// `text` starting at each offset, the distances of 64 consecutive offsets are counted together
+// with one bit per offset in each word
+pub fn hamming_profile(pattern: &[u64], m: usize, text: &[u64], n: usize) -> Vec<u16> {
+    let profiler = Profiler::new(pattern, m, text, n);
+    let mut res = Vec::with_capacity(profiler.offsets);
+    let mut block = [0u16; 64];
+
+    for start in (0..profiler.offsets).step_by(64) {
+        profiler.block(start, usize::MAX, &mut block);
+
+        let end = std::cmp::min(64, profiler.offsets - start);
+        res.extend_from_slice(&block[..end]);
+    }
+
+    res
+}
+
+// the offsets of `text` where the distance to `pattern` is at most `max`, a block of offsets is
+// not counted further once all of them are greater than `max`
+pub fn hamming_matches(
+    pattern: &[u64],
+    m: usize,
+    text: &[u64],
+    n: usize,
+    max: usize,
+) -> HammingMatches {
+    HammingMatches {
+        profiler: Profiler::new(pattern, m, text, n),
+        max,
+        start: 0,
+        block: [0; 64],
+        block_len: 0,
+        pos: 0,
+    }
+}
+
+pub struct HammingMatches {
+    profiler: Profiler,
+    max: usize,
+    // first offset of the next block
+    start: usize,
+    block: [u16; 64],
+    block_len: usize,
+    pos: usize,
+}
+
+impl Iterator for HammingMatches {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            while self.pos < self.block_len {
+                let d = self.block[self.pos] as usize;
+                let offset = self.start - 64 + self.pos;
+                self.pos += 1;
+
+                if d <= self.max {
+                    return Some((offset, d));
+                }
+            }
+
+            if self.start >= self.profiler.offsets {
+                return None;
+            }
+
+            self.block_len = if self.profiler.block(self.start, self.max, &mut self.block) {
+                std::cmp::min(64, self.profiler.offsets - self.start)
+            } else {
+                0
+            };
+            self.pos = 0;
+            self.start += 64;
+        }
+    }
+}
+
+struct Profiler {
+    codes: Vec<u8>,
+    // for each code, one bit per nucleotide of the text that is different
+    masks: [Vec<u64>; 4],
+    // bits of the counters
+    planes: usize,
+    offsets: usize,
+}
+
+impl Profiler {
+    fn new(pattern: &[u64], m: usize, text: &[u64], n: usize) -> Self {
+        if m > u16::MAX as usize {
+            panic!("The length {} of the pattern is greater than 65535!", m);
+        }
+
+        for &(bits, len) in &[(pattern, m), (text, n)] {
+            if len > (bits.len() * 32) {
+                panic!(
+                    "The length {} is greater than the number of nucleotides!",
+                    len
+                );
+            }
+        }
+
+        let words = (n / 32) + if n % 32 == 0 { 0 } else { 1 };
+        let masks = |c: u64| mismatch_mask(&text[..words], &vec![c * 0x5555555555555555; words], n);
+
+        Profiler {
+            codes: Codes::new(pattern, m).collect(),
+            masks: [masks(0), masks(1), masks(2), masks(3)],
+            planes: 64 - (m as u64).leading_zeros() as usize,
+            offsets: if m > n { 0 } else { n - m + 1 },
+        }
+    }
+
+    // the distances of the 64 offsets from `start` in `res`, or `false` as soon as they are all
+    // greater than `max`
+    fn block(&self, start: usize, max: usize, res: &mut [u16; 64]) -> bool {
+        // bit `j` of `planes[b]` is the bit `b` of the distance at `start + j`
+        let mut planes = [0u64; 16];
+
+        for (i, &c) in self.codes.iter().enumerate() {
+            let mut carry = bits_at(&self.masks[c as usize], start + i);
+
+            for plane in planes[..self.planes].iter_mut() {
+                let next = *plane & carry;
+                *plane ^= carry;
+                carry = next;
+
+                if carry == 0 {
+                    break;
+                }
+            }
+
+            if i % 64 == 63 && greater(&planes[..self.planes], max) == !0 {
+                return false;
+            }
+        }
+
+        for (j, d) in res.iter_mut().enumerate() {
+            *d = planes[..self.planes]
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (b, plane)| acc | ((((plane >> j) & 1) as u16) << b));
+        }
+
+        true
+    }
+}
+
+// the lanes of the bit-sliced counters that are greater than `max`, compared from the high bit
+#[inline]
+fn greater(planes: &[u64], max: usize) -> u64 {
+    if max >> planes.len() != 0 {
+        return 0;
+    }
+
+    let mut gt = 0;
+    let mut eq = !0;
+
+    for (b, &plane) in planes.iter().enumerate().rev() {
+        if (max >> b) & 1 == 0 {
+            gt |= eq & plane;
+            eq &= !plane;
+        } else {
+            eq &= plane;
+        }
+    }
+
+    gt
+}
+
+// the 64 bits starting at bit `pos`, bits past the end of `bits` are zeros
+#[inline]
+fn bits_at(bits: &[u64], pos: usize) -> u64 {
+    let idx = pos / 64;
+    let shift = pos % 64;
+
+    if idx >= bits.len() {
+        return 0;
+    }
+
+    let lo = bits[idx] >> shift;
+
+    if shift == 0 || idx + 1 >= bits.len() {
+        lo
+    } else {
+        lo | (bits[idx + 1] << (64 - shift))
+    }
+}
+
+// sequences are compared by tiles of this many rows and columns, so a tile stays in the cache
+const TILE: usize = 64;
+
+// all pairwise hamming distances of the `count` sequences of `len` nucleotides stored one after
+// the other in `seqs`, each padded to a whole number of words, as a row-major `count * count`
+// matrix, the work is split between `threads` workers
+pub fn hamming_matrix(seqs: &[u64], len: usize, count: usize, threads: usize) -> Vec<u32> {
+    check_matrix(seqs, len, count);
+
+    // each pair is computed once and written on both sides of the diagonal
+    let mut res = vec![0u32; count * count];
+    let matrix = Matrix {
+        ptr: res.as_mut_ptr(),
+        count,
+        full: true,
+    };
+
+    fill_matrix(seqs, len, count, threads, matrix);
+
+    res
+}
+
+// same as `hamming_matrix`, but only the distances of the pairs `i < j`, row by row
+pub fn hamming_upper_triangle(seqs: &[u64], len: usize, count: usize, threads: usize) -> Vec<u32> {
+    check_matrix(seqs, len, count);
+
+    let mut res = vec![0u32; upper_offset(count, count)];
+    let matrix = Matrix {
+        ptr: res.as_mut_ptr(),
+        count,
+        full: false,
+    };
+
+    fill_matrix(seqs, len, count, threads, matrix);
+
+    res
+}
+
+fn check_matrix(seqs: &[u64], len: usize, count: usize) {
+    let words = (len / 32) + if len % 32 == 0 { 0 } else { 1 };
+
+    if words * count > seqs.len() {
+        panic!(
+            "The {} sequences of {} nucleotides are greater than the number of nucleotides!",
+            count, len
+        );
+    }
+}
+
+// the output of `fill_matrix`, shared by the workers: a pair is computed by a single worker, so
+// the cells they write never overlap
+#[derive(Clone, Copy)]
+struct Matrix {
+    ptr: *mut u32,
+    count: usize,
+    // `count * count` cells, otherwise only the upper triangle
+    full: bool,
+}
+
+unsafe impl Send for Matrix {}
+unsafe impl Sync for Matrix {}
+
+impl Matrix {
+    // the distance of the pair `i < j`
+    #[inline]
+    unsafe fn set(self, i: usize, j: usize, d: u64) {
+        if self.full {
+            *self.ptr.add(i * self.count + j) = d as u32;
+            *self.ptr.add(j * self.count + i) = d as u32;
+        } else {
+            *self.ptr.add(upper_offset(i, self.count) + j - i - 1) = d as u32;
+        }
+    }
+}
+
+fn fill_matrix(seqs: &[u64], len: usize, count: usize, threads: usize, matrix: Matrix) {
+    let words = (len / 32) + if len % 32 == 0 { 0 } else { 1 };
+
+    if threads <= 1 || count < 2 {
+        matrix_rows(seqs, len, words, count, 0..count, matrix);
+        return;
+    }
+
+    // the rows get shorter, so each worker gets about the same number of pairs rather than rows
+    let per_thread = upper_offset(count, count) / threads + 1;
+    let mut ranges = Vec::with_capacity(threads);
+    let mut start = 0;
+
+    for i in 0..count {
+        if upper_offset(i + 1, count) - upper_offset(start, count) >= per_thread || i == count - 1 {
+            ranges.push(start..(i + 1));
+            start = i + 1;
+        }
+    }
+
+    std::thread::scope(|scope| {
+        for rows in ranges {
+            scope.spawn(move || matrix_rows(seqs, len, words, count, rows, matrix));
+        }
+    });
+}
+
+// the pairs `i < j` of the `rows`, tile by tile
+fn matrix_rows(
+    seqs: &[u64],
+    len: usize,
+    words: usize,
+    count: usize,
+    rows: Range<usize>,
+    matrix: Matrix,
+) {
+    let mut row_tile = rows.start;
+
+    while row_tile < rows.end {
+        let row_end = std::cmp::min(row_tile + TILE, rows.end);
+        let mut col_tile = row_tile + 1;
+
+        while col_tile < count {
+            let col_end = std::cmp::min(col_tile + TILE, count);
+
+            matrix_tile(seqs, len, words, row_tile..row_end, col_tile..col_end, matrix);
+
+            col_tile = col_end;
+        }
+
+        row_tile = row_end;
+    }
+}
+
+fn matrix_tile(
+    seqs: &[u64],
+    len: usize,
+    words: usize,
+    rows: Range<usize>,
+    cols: Range<usize>,
+    matrix: Matrix,
+) {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { matrix_tile_avx(seqs, len, words, rows, cols, matrix) };
+        } else if is_x86_feature_detected!("ssse3") {
+            return unsafe { matrix_tile_sse(seqs, len, words, rows, cols, matrix) };
+        }
+    }
+
+    matrix_tile_scalar(seqs, len, words, rows, cols, matrix)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn matrix_tile_avx(
+    seqs: &[u64],
+    len: usize,
+    words: usize,
+    rows: Range<usize>,
+    cols: Range<usize>,
+    matrix: Matrix,
+) {
+    for i in rows {
+        let a = &seqs[(i * words)..((i + 1) * words)];
+        let mut j = std::cmp::max(cols.start, i + 1);
+
+        while j + 4 <= cols.end {
+            let d = hamming_1x4_avx(a, &seqs[(j * words)..((j + 4) * words)], words, len);
+
+            for (k, &d) in d.iter().enumerate() {
+                matrix.set(i, j + k, d);
+            }
+
+            j += 4;
+        }
+
+        for j in j..cols.end {
+            matrix.set(i, j, hamming_scalar_fast(a, &seqs[(j * words)..], len) as u64);
+        }
+    }
+}
+
+// the distances between `a` and the 4 sequences of `b`, one sequence per lane
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn hamming_1x4_avx(a: &[u64], b: &[u64], words: usize, len: usize) -> [u64; 4] {
+    let end_idx = len / 32;
+
+    let mut res = _mm256_setzero_si256();
+    let mut acc = _mm256_setzero_si256();
+
+    let lut = _mm256_set_epi64x(0x0202020102020201, 0x0202020101010100, 0x0202020102020201, 0x0202020101010100);
+    let mask = _mm256_set1_epi8(0x0F);
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn word(b: &[u64], words: usize, w: usize) -> __m256i {
+        _mm256_set_epi64x(
+            *b.get_unchecked(words * 3 + w) as i64,
+            *b.get_unchecked(words * 2 + w) as i64,
+            *b.get_unchecked(words + w) as i64,
+            *b.get_unchecked(w) as i64,
+        )
+    }
+
+    for w in 0..end_idx {
+        let d = internal_hamming_avx(lut, mask, _mm256_set1_epi64x(*a.get_unchecked(w) as i64), word(b, words, w));
+        acc = _mm256_add_epi8(acc, d);
+
+        // a byte counts at most 4 mismatches per word
+        if w % 63 == 62 {
+            res = _mm256_add_epi64(res, _mm256_sad_epu8(acc, _mm256_setzero_si256()));
+            acc = _mm256_setzero_si256();
+        }
+    }
+
+    let leftover = (len % 32) * 2;
+
+    if leftover > 0 {
+        let m = _mm256_set1_epi64x((1i64 << leftover) - 1);
+        let a = _mm256_and_si256(_mm256_set1_epi64x(*a.get_unchecked(end_idx) as i64), m);
+        let b = _mm256_and_si256(word(b, words, end_idx), m);
+        acc = _mm256_add_epi8(acc, internal_hamming_avx(lut, mask, a, b));
+    }
+
+    res = _mm256_add_epi64(res, _mm256_sad_epu8(acc, _mm256_setzero_si256()));
+
+    union A {
+        v: __m256i,
+        a: [u64; 4]
+    }
+
+    A { v: res }.a
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+unsafe fn matrix_tile_sse(
+    seqs: &[u64],
+    len: usize,
+    words: usize,
+    rows: Range<usize>,
+    cols: Range<usize>,
+    matrix: Matrix,
+) {
+    for i in rows {
+        let a = &seqs[(i * words)..((i + 1) * words)];
+        let mut j = std::cmp::max(cols.start, i + 1);
+
+        while j + 2 <= cols.end {
+            let d = hamming_1x2_sse(a, &seqs[(j * words)..((j + 2) * words)], words, len);
+
+            matrix.set(i, j, d[0]);
+            matrix.set(i, j + 1, d[1]);
+
+            j += 2;
+        }
+
+        for j in j..cols.end {
+            matrix.set(i, j, hamming_scalar_fast(a, &seqs[(j * words)..], len) as u64);
+        }
+    }
+}
+
+// the distances between `a` and the 2 sequences of `b`, one sequence per lane
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+#[inline]
+unsafe fn hamming_1x2_sse(a: &[u64], b: &[u64], words: usize, len: usize) -> [u64; 2] {
+    let end_idx = len / 32;
+
+    let mut res = _mm_setzero_si128();
+    let mut acc = _mm_setzero_si128();
+
+    let lut = _mm_set_epi64x(0x0202020102020201, 0x0202020101010100);
+    let mask = _mm_set1_epi8(0x0F);
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[target_feature(enable = "ssse3")]
+    #[inline]
+    unsafe fn word(b: &[u64], words: usize, w: usize) -> __m128i {
+        _mm_set_epi64x(*b.get_unchecked(words + w) as i64, *b.get_unchecked(w) as i64)
+    }
+
+    for w in 0..end_idx {
+        let d = internal_hamming_sse(lut, mask, _mm_set1_epi64x(*a.get_unchecked(w) as i64), word(b, words, w));
+        acc = _mm_add_epi8(acc, d);
+
+        // a byte counts at most 4 mismatches per word
+        if w % 63 == 62 {
+            res = _mm_add_epi64(res, _mm_sad_epu8(acc, _mm_setzero_si128()));
+            acc = _mm_setzero_si128();
+        }
+    }
+
+    let leftover = (len % 32) * 2;
+
+    if leftover > 0 {
+        let m = _mm_set1_epi64x((1i64 << leftover) - 1);
+        let a = _mm_and_si128(_mm_set1_epi64x(*a.get_unchecked(end_idx) as i64), m);
+        let b = _mm_and_si128(word(b, words, end_idx), m);
+        acc = _mm_add_epi8(acc, internal_hamming_sse(lut, mask, a, b));
+    }
+
+    res = _mm_add_epi64(res, _mm_sad_epu8(acc, _mm_setzero_si128()));
+
+    union A {
+        v: __m128i,
+        a: [u64; 2]
+    }
+
+    A { v: res }.a
+}
+
+fn matrix_tile_scalar(
+    seqs: &[u64],
+    len: usize,
+    words: usize,
+    rows: Range<usize>,
+    cols: Range<usize>,
+    matrix: Matrix,
+) {
+    for i in rows {
+        let a = &seqs[(i * words)..((i + 1) * words)];
+
+        for j in std::cmp::max(cols.start, i + 1)..cols.end {
+            unsafe { matrix.set(i, j, hamming_scalar_fast(a, &seqs[(j * words)..], len) as u64) };
+        }
+    }
+}
+
+// index of the first pair of row `i` in the upper triangle
+#[inline]
+fn upper_offset(i: usize, count: usize) -> usize {
+    i * count - i * (i + 1) / 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::random_nuc;
+
+    #[test]
+    fn test_hamming_scalar() {
+        assert_eq!(hamming_scalar(&vec![0x0101010101010101; 128], &vec![0x0101010101010100; 128], 4096), 128);
+        assert_eq!(hamming_scalar_fast(&vec![0x0101010101010101; 128], &vec![0x0101010101010100; 128], 4096), 128);
+        assert_eq!(hamming_scalar(&vec![0b010101], &vec![0b010100], 3), 1);
+        assert_eq!(hamming_scalar_fast(&vec![0b010101], &vec![0b010100], 3), 1);
+    }
+
+    #[test]
+    fn test_hamming_scalar_bounded() {
+        assert_eq!(hamming_scalar_bounded(&vec![0x0101010101010101; 128], &vec![0x0101010101010100; 128], 4096, 128), Some(128));
+        assert_eq!(hamming_scalar_bounded(&vec![0x0101010101010101; 128], &vec![0x0101010101010100; 128], 4096, 127), None);
+        assert_eq!(hamming_scalar_bounded(&vec![0b010101], &vec![0b010100], 3, 1), Some(1));
+        assert_eq!(hamming_scalar_bounded(&vec![0b010101], &vec![0b010100], 3, 0), None);
+        assert_eq!(hamming_scalar_bounded(&vec![0b010101], &vec![0b010101], 3, 0), Some(0));
+    }
+
+    #[test]
+    fn test_hamming_shifted_scalar() {
+        assert_eq!(hamming_shifted_scalar(&vec![0b01010100], 1, &vec![0b010100], 0, 3), 1);
+        assert_eq!(hamming_shifted_scalar(&vec![0x0101010101010101; 128], 4, &vec![0x0101010101010100; 128], 0, 4000), 125);
+        assert_eq!(hamming_shifted_scalar(&vec![0x0101010101010101; 128], 4, &vec![0x0101010101010101; 128], 0, 4000), 0);
+    }
+
+    #[test]
+    fn test_hamming_avx() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                assert_eq!(unsafe { hamming_avx(&vec![0x0101010101010101; 128], &vec![0x0101010101010100; 128], 4096) }, 128);
+                assert_eq!(unsafe { hamming_avx(&vec![0b010101], &vec![0b010100], 3) }, 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hamming_sse() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("ssse3") {
+                assert_eq!(unsafe { hamming_sse(&vec![0x0101010101010101; 128], &vec![0x0101010101010100; 128], 4096) }, 128);
+                assert_eq!(unsafe { hamming_sse(&vec![0b010101], &vec![0b010100], 3) }, 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hamming_bounded_avx() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                assert_eq!(unsafe { hamming_bounded_avx(&vec![0x0101010101010101; 128], &vec![0x0101010101010100; 128], 4096, 128) }, Some(128));
+                assert_eq!(unsafe { hamming_bounded_avx(&vec![0x0101010101010101; 128], &vec![0x0101010101010100; 128], 4096, 127) }, None);
+                assert_eq!(unsafe { hamming_bounded_avx(&vec![0x0101010101010101; 131], &vec![0x0101010101010100; 131], 4190, 131) }, Some(131));
+                assert_eq!(unsafe { hamming_bounded_avx(&vec![0x0101010101010101; 131], &vec![0x0101010101010100; 131], 4190, 130) }, None);
+                assert_eq!(unsafe { hamming_bounded_avx(&vec![0b010101], &vec![0b010100], 3, 0) }, None);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hamming_bounded_sse() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("ssse3") {
+                assert_eq!(unsafe { hamming_bounded_sse(&vec![0x0101010101010101; 128], &vec![0x0101010101010100; 128], 4096, 128) }, Some(128));
+                assert_eq!(unsafe { hamming_bounded_sse(&vec![0x0101010101010101; 128], &vec![0x0101010101010100; 128], 4096, 127) }, None);
+                assert_eq!(unsafe { hamming_bounded_sse(&vec![0x0101010101010101; 131], &vec![0x0101010101010100; 131], 4190, 131) }, Some(131));
+                assert_eq!(unsafe { hamming_bounded_sse(&vec![0x0101010101010101; 131], &vec![0x0101010101010100; 131], 4190, 130) }, None);
+                assert_eq!(unsafe { hamming_bounded_sse(&vec![0b010101], &vec![0b010100], 3, 0) }, None);
+            }
+        }
+    }
+
+    fn shifted(bits: &[u64], offset: usize, len: usize) -> Vec<u64> {
+        (0..((len / 32) + if len % 32 == 0 { 0 } else { 1 })).map(|i| word_at(bits, offset + i * 32)).collect()
+    }
+
+    fn test_hamming_at_with(f: unsafe fn(&[u64], usize, &[u64], usize, usize) -> usize) {
+        let a = (0..200u64).map(|i| i.wrapping_mul(0x9E3779B97F4A7C15)).collect::<Vec<_>>();
+        let b = (0..200u64).map(|i| (i + 7).wrapping_mul(0xC2B2AE3D27D4EB4F)).collect::<Vec<_>>();
+
+        for &(a_offset, b_offset) in &[(0, 0), (1, 0), (0, 31), (5, 5), (33, 70), (64, 95), (31, 1)] {
+            for &len in &[0, 1, 31, 32, 33, 600, 2048, 4000, 6300 - 95] {
+                let expected = hamming_scalar(&shifted(&a, a_offset, len), &shifted(&b, b_offset, len), len);
+                assert_eq!(unsafe { f(&a, a_offset, &b, b_offset, len) }, expected);
+            }
+        }
+
+        // the whole sequences, so the scalar part reads the last words
+        assert_eq!(unsafe { f(&a, 0, &b, 0, 6400) }, hamming_scalar(&a, &b, 6400));
+        assert_eq!(unsafe { f(&a, 40, &b, 40, 6360) }, hamming_scalar(&shifted(&a, 40, 6360), &shifted(&b, 40, 6360), 6360));
+    }
+
+    #[test]
+    fn test_hamming_at_scalar() {
+        test_hamming_at_with(hamming_shifted_scalar);
+    }
+
+    #[test]
+    fn test_hamming_at_avx() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                test_hamming_at_with(hamming_at_avx);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hamming_at_sse() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("ssse3") {
+                test_hamming_at_with(hamming_at_sse);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "The range 10..75 is out of the 64 nucleotides!")]
+    fn test_hamming_at_out_of_range() {
+        hamming_at(&[0; 4], 0, &[0; 2], 10, 65);
+    }
+
+    // a text of several words with a few mutated copies of its nucleotides `20..(20 + m)`
+    fn profile_nuc(m: usize) -> (Vec<u8>, Vec<u8>) {
+        let mut text = random_nuc(1000, 19);
+        let pattern = text[20..(20 + m)].to_vec();
+
+        for &(start, mutations) in &[(300, 1), (517, 3), (1000 - m, 0)] {
+            text[start..(start + m)].copy_from_slice(&pattern);
+
+            for k in 0..std::cmp::min(mutations, m) {
+                let i = start + (k * 7) % m;
+                text[i] = if text[i] == b'A' { b'C' } else { b'A' };
+            }
+        }
+
+        (pattern, text)
+    }
+
+    fn naive_profile(pattern: &[u8], text: &[u8]) -> Vec<u16> {
+        let mut res = Vec::new();
+
+        for i in 0..=(text.len() - pattern.len()) {
+            let mut d = 0;
+
+            for j in 0..pattern.len() {
+                if text[i + j] != pattern[j] {
+                    d += 1;
+                }
+            }
+
+            res.push(d);
+        }
+
+        res
+    }
+
+    #[test]
+    fn test_hamming_profile() {
+        for &m in &[0, 1, 5, 31, 32, 33, 63, 64, 65, 200] {
+            let (pattern, text) = profile_nuc(m);
+            let res = hamming_profile(&crate::nuc2bit::encode(&pattern), m, &crate::nuc2bit::encode(&text), text.len());
+
+            assert_eq!(res, naive_profile(&pattern, &text));
+            assert_eq!(res[20], 0);
+        }
+
+        // the words after the text are ignored
+        let text = crate::nuc2bit::encode(b"ACGTACGTTT");
+        assert_eq!(hamming_profile(&crate::nuc2bit::encode(b"GTA"), 3, &text, 8), vec![3, 3, 0, 3, 3, 3]);
+        assert_eq!(hamming_profile(&text, 10, &text, 8), vec![]);
+    }
+
+    #[test]
+    fn test_hamming_matches() {
+        for &m in &[1, 5, 33, 64, 200] {
+            let (pattern, text) = profile_nuc(m);
+            let pattern_bits = crate::nuc2bit::encode(&pattern);
+            let text_bits = crate::nuc2bit::encode(&text);
+            let profile = naive_profile(&pattern, &text);
+
+            for &max in &[0, 1, 3, m / 2, m] {
+                let res = hamming_matches(&pattern_bits, m, &text_bits, text.len(), max).collect::<Vec<_>>();
+                let expected = profile
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &d)| d as usize <= max)
+                    .map(|(i, &d)| (i, d as usize))
+                    .collect::<Vec<_>>();
+
+                assert_eq!(res, expected);
+            }
+        }
+
+        let (pattern, text) = profile_nuc(200);
+        let res = hamming_matches(&crate::nuc2bit::encode(&pattern), 200, &crate::nuc2bit::encode(&text), 1000, 3);
+        assert_eq!(res.collect::<Vec<_>>(), vec![(20, 0), (300, 1), (517, 3), (800, 0)]);
+    }
+
+    #[test]
+    fn test_greater() {
+        // counters 0..8 in the lanes 0..8
+        let planes = [0b10101010, 0b11001100, 0b11110000];
+
+        assert_eq!(greater(&planes, 4), 0b11100000);
+        assert_eq!(greater(&planes, 0), 0b11111110);
+        assert_eq!(greater(&planes, 7), 0);
+        assert_eq!(greater(&planes, 100), 0);
+    }
+
+    fn matrix_seqs(count: usize, len: usize) -> Vec<u64> {
+        (0..count).flat_map(|i| crate::nuc2bit::encode(&random_nuc(len, i as u64))).collect()
+    }
+
+    #[test]
+    fn test_hamming_matrix() {
+        for &(count, len) in &[(0, 10), (1, 10), (5, 31), (70, 100), (131, 150), (150, 600), (10, 2100)] {
+            let words = (len / 32) + if len % 32 == 0 { 0 } else { 1 };
+            let seqs = matrix_seqs(count, len);
+            let seq = |i: usize| &seqs[(i * words)..((i + 1) * words)];
+
+            let mut expected = vec![0u32; count * count];
+            let mut expected_upper = Vec::new();
+
+            for i in 0..count {
+                for j in 0..count {
+                    expected[i * count + j] = hamming_scalar(seq(i), seq(j), len) as u32;
+
+                    if i < j {
+                        expected_upper.push(expected[i * count + j]);
+                    }
+                }
+            }
+
+            for &threads in &[0, 1, 3, 8] {
+                assert_eq!(hamming_matrix(&seqs, len, count, threads), expected);
+                assert_eq!(hamming_upper_triangle(&seqs, len, count, threads), expected_upper);
+            }
+        }
+    }
+
+    type Tile = unsafe fn(&[u64], usize, usize, Range<usize>, Range<usize>, Matrix);
+
+    fn test_matrix_tile_with(f: Tile) {
+        for &(count, len) in &[(9, 31), (9, 32), (9, 100), (7, 2100)] {
+            let words = (len / 32) + if len % 32 == 0 { 0 } else { 1 };
+            let seqs = matrix_seqs(count, len);
+
+            let mut res = vec![0u32; count * count];
+            let matrix = Matrix {
+                ptr: res.as_mut_ptr(),
+                count,
+                full: true,
+            };
+
+            // a single tile and the same pairs split in tiles that do not start on the diagonal
+            unsafe { f(&seqs, len, words, 0..count, 0..count, matrix) };
+            let single = res.clone();
+            res.iter_mut().for_each(|d| *d = 0);
+
+            unsafe {
+                f(&seqs, len, words, 0..4, 0..count, matrix);
+                f(&seqs, len, words, 4..count, 4..6, matrix);
+                f(&seqs, len, words, 4..count, 6..count, matrix);
+            }
+
+            for i in 0..count {
+                for j in 0..count {
+                    let d = hamming_scalar(&seqs[(i * words)..], &seqs[(j * words)..], len) as u32;
+                    assert_eq!(single[i * count + j], d);
+                    assert_eq!(res[i * count + j], d);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_matrix_tile_scalar() {
+        test_matrix_tile_with(matrix_tile_scalar);
+    }
+
+    #[test]
+    fn test_matrix_tile_avx() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                test_matrix_tile_with(matrix_tile_avx);
+            }
+        }
+    }
+
+    #[test]
+    fn test_matrix_tile_sse() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("ssse3") {
+                test_matrix_tile_with(matrix_tile_sse);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "The 3 sequences of 40 nucleotides are greater than the number of nucleotides!")]
+    fn test_hamming_matrix_too_short() {
+        hamming_matrix(&[0; 5], 40, 3, 1);
+    }
+
+    fn ts_tv_nuc(len: usize) -> (Vec<u8>, Vec<u8>, (usize, usize)) {
+        let a = random_nuc(len, 1);
+        let b = random_nuc(len, 2);
+
+        let purine = |n: u8| n == b'A' || n == b'G';
+        let ts = a.iter().zip(&b).filter(|&(&x, &y)| x != y && purine(x) == purine(y)).count();
+        let tv = a.iter().zip(&b).filter(|&(&x, &y)| purine(x) != purine(y)).count();
+
+        (a, b, (ts, tv))
+    }
+
+    #[test]
+    fn test_hamming_ts_tv_scalar() {
+        assert_eq!(hamming_ts_tv_scalar(&crate::nuc2bit::encode(b"ACGTACGT"), &crate::nuc2bit::encode(b"GTAGCAGA"), 8), (3, 4));
+
+        for &len in &[0, 1, 31, 32, 33, 100] {
+            let (a, b, res) = ts_tv_nuc(len);
+            assert_eq!(hamming_ts_tv_scalar(&crate::nuc2bit::encode(&a), &crate::nuc2bit::encode(&b), len), res);
+        }
+    }
+
+    #[test]
+    fn test_hamming_ts_tv_avx() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                for &len in &[0, 100, 4096, 4190, 9000] {
+                    let (a, b, res) = ts_tv_nuc(len);
+                    let (a, b) = (crate::nuc2bit::encode(&a), crate::nuc2bit::encode(&b));
+                    assert_eq!(unsafe { hamming_ts_tv_avx(&a, &b, len) }, res);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_hamming_ts_tv_sse() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("ssse3") {
+                for &len in &[0, 100, 2048, 4190, 9000] {
+                    let (a, b, res) = ts_tv_nuc(len);
+                    let (a, b) = (crate::nuc2bit::encode(&a), crate::nuc2bit::encode(&b));
+                    assert_eq!(unsafe { hamming_ts_tv_sse(&a, &b, len) }, res);
+                }
             }
         }
     }
@@ -0,0 +1,348 @@
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use crate::utils::word_at;
+
+// k-mers are right-aligned with the same order as the packed sequence, so the first nucleotide is
+// in the low bits
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Kmer {
+    pub forward: u64,
+    pub reverse: u64,
+    // the smaller of `forward` and `reverse`
+    pub canonical: u64,
+}
+
+pub struct Kmers<'a> {
+    bits: &'a [u64],
+    len: usize,
+    k: usize,
+    pos: usize,
+    forward: u64,
+    reverse: u64,
+    mask: u64,
+}
+
+impl<'a> Kmers<'a> {
+    pub fn new(bits: &'a [u64], len: usize, k: usize) -> Self {
+        check_k(k, 32);
+
+        if len > (bits.len() * 32) {
+            panic!(
+                "The length {} is greater than the number of nucleotides!",
+                len
+            );
+        }
+
+        let mut kmers = Kmers {
+            bits,
+            len,
+            k,
+            pos: 0,
+            forward: 0,
+            reverse: 0,
+            mask: mask(k),
+        };
+
+        // the first k-mer is completed by the first call to `next`
+        while kmers.pos + 1 < k && kmers.pos < len {
+            kmers.push();
+        }
+
+        kmers
+    }
+
+    #[inline]
+    fn push(&mut self) {
+        let c = (self.bits[self.pos / 32] >> ((self.pos % 32) * 2)) & 0b11;
+
+        self.forward = (self.forward >> 2) | (c << ((self.k - 1) * 2));
+        // complement by flipping the high bit: A <-> T and C <-> G
+        self.reverse = ((self.reverse << 2) | (c ^ 0b10)) & self.mask;
+        self.pos += 1;
+    }
+}
+
+impl<'a> Iterator for Kmers<'a> {
+    type Item = Kmer;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.len {
+            return None;
+        }
+
+        self.push();
+
+        Some(Kmer {
+            forward: self.forward,
+            reverse: self.reverse,
+            canonical: self.forward.min(self.reverse),
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len - self.pos;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for Kmers<'a> {}
+
+// the k-mer of the nucleotides `pos..(pos + k)`, for k up to 32
+pub fn kmer_at(bits: &[u64], pos: usize, k: usize) -> u64 {
+    check_k(k, 32);
+    check_range(bits, pos, k);
+
+    word_at(bits, pos) & mask(k)
+}
+
+// same as `kmer_at`, for k up to 64
+pub fn kmer_at_u128(bits: &[u64], pos: usize, k: usize) -> u128 {
+    check_k(k, 64);
+    check_range(bits, pos, k);
+
+    let lo = word_at(bits, pos) as u128;
+
+    if k <= 32 {
+        lo & (mask(k) as u128)
+    } else {
+        let hi = (word_at(bits, pos + 32) & mask(k - 32)) as u128;
+        lo | (hi << 64)
+    }
+}
+
+// the k-mers starting at each of the `positions`, for k up to 32
+pub fn kmers_at(bits: &[u64], positions: &[usize], k: usize) -> Vec<u64> {
+    check_k(k, 32);
+
+    if let Some(&max) = positions.iter().max() {
+        check_range(bits, max, k);
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { kmers_at_avx(bits, positions, k) };
+        }
+    }
+
+    kmers_at_scalar(bits, positions, k)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn kmers_at_avx(bits: &[u64], positions: &[usize], k: usize) -> Vec<u64> {
+    let end_idx = positions.len() / 4;
+
+    let mut res: Vec<u64> = Vec::with_capacity(positions.len());
+    let res_ptr = res.as_mut_ptr() as *mut __m256i;
+    let ptr = bits.as_ptr() as *const i64;
+
+    let k_mask = _mm256_set1_epi64x(mask(k) as i64);
+    let last = _mm256_set1_epi64x(bits.len() as i64 - 1);
+    let nuc_mask = _mm256_set1_epi64x(31);
+
+    for i in 0..end_idx {
+        let p = &positions[(i * 4)..(i * 4 + 4)];
+        let pos = _mm256_set_epi64x(p[3] as i64, p[2] as i64, p[1] as i64, p[0] as i64);
+
+        let lo_idx = _mm256_srli_epi64(pos, 5);
+        // the word after the last one is never needed but must not be read, the indexes are
+        // small and positive so the 32-bit min is enough
+        let hi_idx = _mm256_min_epi32(_mm256_add_epi64(lo_idx, _mm256_set1_epi64x(1)), last);
+        let lo = _mm256_i64gather_epi64(ptr, lo_idx, 8);
+        let hi = _mm256_i64gather_epi64(ptr, hi_idx, 8);
+
+        // a shift by 64 gives 0, so k-mers that are aligned on a word do not need a special case
+        let shift = _mm256_slli_epi64(_mm256_and_si256(pos, nuc_mask), 1);
+        let v = _mm256_or_si256(
+            _mm256_srlv_epi64(lo, shift),
+            _mm256_sllv_epi64(hi, _mm256_sub_epi64(_mm256_set1_epi64x(64), shift)),
+        );
+
+        _mm256_storeu_si256(res_ptr.add(i), _mm256_and_si256(v, k_mask));
+    }
+
+    res.set_len(end_idx * 4);
+    res.extend(positions[(end_idx * 4)..].iter().map(|&pos| word_at(bits, pos) & mask(k)));
+
+    res
+}
+
+fn kmers_at_scalar(bits: &[u64], positions: &[usize], k: usize) -> Vec<u64> {
+    positions.iter().map(|&pos| word_at(bits, pos) & mask(k)).collect()
+}
+
+#[inline]
+fn mask(k: usize) -> u64 {
+    if k == 32 {
+        !0
+    } else {
+        (1 << (k * 2)) - 1
+    }
+}
+
+fn check_k(k: usize, max: usize) {
+    if k == 0 || k > max {
+        panic!("The k-mer size {} is not between 1 and {}!", k, max);
+    }
+}
+
+fn check_range(bits: &[u64], pos: usize, k: usize) {
+    if pos + k > (bits.len() * 32) {
+        panic!(
+            "The range {}..{} is out of the {} nucleotides!",
+            pos,
+            pos + k,
+            bits.len() * 32
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nuc2bit::encode;
+
+    fn naive(nuc: &[u8], k: usize) -> Vec<Kmer> {
+        let comp = |n: &u8| match n {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            _ => b'A',
+        };
+
+        nuc.windows(k)
+            .map(|w| {
+                let forward = encode(w)[0];
+                let rc = w.iter().rev().map(comp).collect::<Vec<u8>>();
+                let reverse = encode(&rc)[0];
+
+                Kmer {
+                    forward,
+                    reverse,
+                    canonical: forward.min(reverse),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_kmers() {
+        let nuc = b"ACGTTGCAACGTAAGGCCTTATATGCGCACGTACGTAGGCTTAAACCCGGGTTTAGCT";
+        let bits = encode(nuc);
+
+        for k in 1..=32 {
+            assert_eq!(Kmers::new(&bits, nuc.len(), k).collect::<Vec<_>>(), naive(nuc, k));
+            assert_eq!(Kmers::new(&bits, nuc.len(), k).len(), nuc.len() - k + 1);
+        }
+
+        assert_eq!(Kmers::new(&bits, 10, 4).count(), 7);
+        assert_eq!(Kmers::new(&bits, 3, 4).count(), 0);
+        assert_eq!(Kmers::new(&bits, 0, 4).count(), 0);
+    }
+
+    #[test]
+    fn test_kmers_canonical() {
+        let bits = encode(b"ACGTAAC");
+        let kmers = Kmers::new(&bits, 7, 4).collect::<Vec<_>>();
+
+        // ACGT is its own reverse complement
+        assert_eq!(kmers[0].forward, kmers[0].reverse);
+        assert_eq!(kmers[0].forward, 0b10110100);
+        // GTAA and TTAC
+        assert_eq!(kmers[2].forward, 0b00001011);
+        assert_eq!(kmers[2].reverse, 0b01001010);
+        assert_eq!(kmers[2].canonical, 0b00001011);
+    }
+
+    fn kmers_all(bits: &[u64], len: usize, k: usize) -> Vec<u64> {
+        Kmers::new(bits, len, k).map(|kmer| kmer.forward).collect()
+    }
+
+    #[test]
+    fn test_kmer_at() {
+        let nuc = b"ACGTTGCAACGTAAGGCCTTATATGCGCACGTACGTAGGCTTAAACCCGGGTTTAGCT";
+        let bits = encode(nuc);
+
+        for k in 1..=32 {
+            let kmers = (0..=(nuc.len() - k)).map(|pos| kmer_at(&bits, pos, k)).collect::<Vec<_>>();
+            assert_eq!(kmers, kmers_all(&bits, nuc.len(), k));
+        }
+
+        assert_eq!(kmer_at(&bits, 56, 2), 0b1001);
+    }
+
+    #[test]
+    #[should_panic(expected = "The range 60..65 is out of the 64 nucleotides!")]
+    fn test_kmer_at_out_of_range() {
+        kmer_at(&encode(b"ACGTTGCAACGTAAGGCCTTATATGCGCACGTACGTAGGCTTAAACCCGGGTTTAGCT"), 60, 5);
+    }
+
+    #[test]
+    fn test_kmer_at_u128() {
+        let nuc = b"ACGTTGCAACGTAAGGCCTTATATGCGCACGTACGTAGGCTTAAACCCGGGTTTAGCTTGACCAGTCAGTTTTTGCA";
+        let bits = encode(nuc);
+
+        for k in 1..=64 {
+            for pos in 0..=(nuc.len() - k) {
+                assert_eq!(
+                    kmer_at_u128(&bits, pos, k),
+                    crate::nuc2bit::encode(&nuc[pos..(pos + k)])
+                        .iter()
+                        .enumerate()
+                        .fold(0u128, |acc, (i, &w)| acc | ((w as u128) << (i * 64)))
+                );
+            }
+        }
+    }
+
+    fn positions(n: usize) -> Vec<usize> {
+        (0..n).map(|i| (i * 37) % (n - 5)).collect()
+    }
+
+    #[test]
+    fn test_kmers_at_scalar() {
+        let nuc = b"ACGTTGCAACGTAAGGCCTTATATGCGCACGTACGTAGGCTTAAACCCGGGTTTAGCTTGACCAGTCAGTTTTTGCA";
+        let bits = encode(nuc);
+        let pos = positions(nuc.len());
+
+        for k in 1..=5 {
+            let res = pos.iter().map(|&p| kmer_at(&bits, p, k)).collect::<Vec<_>>();
+            assert_eq!(kmers_at_scalar(&bits, &pos, k), res);
+        }
+    }
+
+    #[test]
+    fn test_kmers_at_avx() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                let nuc = b"ACGTTGCAACGTAAGGCCTTATATGCGCACGTACGTAGGCTTAAACCCGGGTTTAGCTTGACCAGTCAGTTTTTGCA";
+                let bits = encode(nuc);
+                let pos = positions(nuc.len());
+
+                for k in 1..=5 {
+                    let res = pos.iter().map(|&p| kmer_at(&bits, p, k)).collect::<Vec<_>>();
+
+                    for n in 0..pos.len() {
+                        assert_eq!(unsafe { kmers_at_avx(&bits, &pos[..n], k) }, &res[..n]);
+                    }
+                }
+
+                // the last word is read as the low word, but never as the high word
+                let bits = encode(&nuc[..64]);
+                assert_eq!(unsafe { kmers_at_avx(&bits, &[32, 40, 59, 63], 1) }, vec![0, 2, 3, 0]);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "The k-mer size 33 is not between 1 and 32!")]
+    fn test_kmers_too_long() {
+        Kmers::new(&[0], 32, 33);
+    }
+}
@@ -1,7 +1,24 @@
+// `is_multiple_of` is newer than our MSRV, and the kernels spell out `idx + 0` and `ptr.offset(i as isize)` on purpose
+// to keep the unrolled loops readable
+#![allow(clippy::manual_is_multiple_of, clippy::identity_op, clippy::ptr_offset_with_cast)]
+#![cfg_attr(test, allow(clippy::useless_vec, clippy::bool_assert_comparison))]
+#![cfg_attr(feature = "bench-internals", allow(clippy::missing_safety_doc))]
+
 pub mod nuc2bit;
+pub mod nuc2nibble;
 pub mod bit2nuc;
 pub mod complement;
 pub mod utils;
 pub mod popcount;
 pub mod hamming;
 pub mod check;
+pub mod packed;
+pub mod kmer;
+pub mod minimizer;
+pub mod syncmer;
+pub mod nthash;
+pub mod edit_distance;
+pub mod mismatch;
+pub mod base_counts;
+pub mod composition;
+pub mod twobit;
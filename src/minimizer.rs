@@ -0,0 +1,239 @@
+use crate::kmer::Kmers;
+
+use std::collections::VecDeque;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Order {
+    // A < C < G < T, the first nucleotide being the most significant
+    Lexicographic,
+    // hash of the forward k-mer
+    Random,
+    // hash of the canonical k-mer, so both strands select the same k-mers
+    Canonical,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Minimizer {
+    pub pos: usize,
+    // the canonical k-mer with `Order::Canonical`, the forward one otherwise
+    pub kmer: u64,
+}
+
+// the smallest k-mer of each window of `w` consecutive k-mers, a minimizer shared by consecutive
+// windows is only returned once and ties are broken by the leftmost k-mer
+pub struct Minimizers<'a> {
+    kmers: Kmers<'a>,
+    k: usize,
+    w: usize,
+    order: Order,
+    pos: usize,
+    // increasing keys of the k-mers that can still be the minimizer of a window
+    queue: VecDeque<(u64, usize, u64)>,
+    last: Option<usize>,
+}
+
+impl<'a> Minimizers<'a> {
+    pub fn new(bits: &'a [u64], len: usize, k: usize, w: usize, order: Order) -> Self {
+        if w == 0 {
+            panic!("The window size must be at least 1!");
+        }
+
+        Minimizers {
+            kmers: Kmers::new(bits, len, k),
+            k,
+            w,
+            order,
+            pos: 0,
+            queue: VecDeque::with_capacity(w),
+            last: None,
+        }
+    }
+}
+
+impl<'a> Iterator for Minimizers<'a> {
+    type Item = Minimizer;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let kmer = self.kmers.next()?;
+            let pos = self.pos;
+            self.pos += 1;
+
+            let (key, value) = match self.order {
+                Order::Lexicographic => (lexicographic_key(kmer.forward, self.k), kmer.forward),
+                Order::Random => (hash(kmer.forward), kmer.forward),
+                Order::Canonical => (hash(kmer.canonical), kmer.canonical),
+            };
+
+            while let Some(&(back, _, _)) = self.queue.back() {
+                if back <= key {
+                    break;
+                }
+
+                self.queue.pop_back();
+            }
+            self.queue.push_back((key, pos, value));
+
+            while let Some(&(_, front, _)) = self.queue.front() {
+                if front + self.w > pos {
+                    break;
+                }
+
+                self.queue.pop_front();
+            }
+
+            if pos + 1 >= self.w {
+                let (_, min_pos, min_value) = self.queue[0];
+
+                if self.last != Some(min_pos) {
+                    self.last = Some(min_pos);
+
+                    return Some(Minimizer {
+                        pos: min_pos,
+                        kmer: min_value,
+                    });
+                }
+            }
+        }
+    }
+}
+
+// reverse the order of the nucleotides and swap the codes of G and T
+#[inline]
+fn lexicographic_key(kmer: u64, k: usize) -> u64 {
+    let x = kmer ^ ((kmer >> 1) & 0x5555555555555555);
+
+    let x = x.swap_bytes();
+    let x = ((x >> 4) & 0x0F0F0F0F0F0F0F0F) | ((x & 0x0F0F0F0F0F0F0F0F) << 4);
+    let x = ((x >> 2) & 0x3333333333333333) | ((x & 0x3333333333333333) << 2);
+
+    x >> (64 - k * 2)
+}
+
+// murmur3 finalizer, which is a bijection
+#[inline]
+pub(crate) fn hash(kmer: u64) -> u64 {
+    let mut x = kmer;
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xFF51AFD7ED558CCD);
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xC4CEB9FE1A85EC53);
+    x ^ (x >> 33)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nuc2bit::encode;
+
+    const NUC: &[u8] = b"ACGTTGCAACGTAAGGCCTTATATGCGCACGTACGTAGGCTTAAACCCGGGTTTAGCTTGACCAGTCAGTTTTTGCAAAAAA";
+
+    fn naive(nuc: &[u8], k: usize, w: usize, order: Order) -> Vec<Minimizer> {
+        let bits = encode(nuc);
+        let kmers = Kmers::new(&bits, nuc.len(), k)
+            .map(|kmer| match order {
+                Order::Lexicographic => (lexicographic_key(kmer.forward, k), kmer.forward),
+                Order::Random => (hash(kmer.forward), kmer.forward),
+                Order::Canonical => (hash(kmer.canonical), kmer.canonical),
+            })
+            .collect::<Vec<_>>();
+
+        let mut res: Vec<Minimizer> = Vec::new();
+
+        for (start, window) in kmers.windows(w).enumerate() {
+            let (i, &(_, kmer)) = window
+                .iter()
+                .enumerate()
+                .min_by_key(|&(i, &(key, _))| (key, i))
+                .unwrap();
+            let min = Minimizer {
+                pos: start + i,
+                kmer,
+            };
+
+            if res.last() != Some(&min) {
+                res.push(min);
+            }
+        }
+
+        res
+    }
+
+    #[test]
+    fn test_lexicographic_key() {
+        assert_eq!(lexicographic_key(encode(b"ACGT")[0], 4), 0b00011011);
+        assert_eq!(lexicographic_key(encode(b"TGCA")[0], 4), 0b11100100);
+
+        let mut kmers = vec![b"GATT", b"ACGT", b"TTTT", b"AAAA", b"CAGT", b"GTAC", b"ACTG"];
+        kmers.sort_by_key(|kmer| lexicographic_key(encode(&kmer[..])[0], 4));
+        assert_eq!(kmers, vec![b"AAAA", b"ACGT", b"ACTG", b"CAGT", b"GATT", b"GTAC", b"TTTT"]);
+    }
+
+    #[test]
+    fn test_minimizers() {
+        let bits = encode(NUC);
+
+        for &order in &[Order::Lexicographic, Order::Random, Order::Canonical] {
+            for k in &[1, 3, 5, 15, 32] {
+                for w in &[1, 2, 4, 10, 40] {
+                    assert_eq!(
+                        Minimizers::new(&bits, NUC.len(), *k, *w, order).collect::<Vec<_>>(),
+                        naive(NUC, *k, *w, order)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_minimizers_lexicographic() {
+        let bits = encode(b"TTGCATTTGA");
+        let res = Minimizers::new(&bits, 10, 3, 3, Order::Lexicographic).collect::<Vec<_>>();
+
+        // TTG TGC GCA CAT ATT TTT TTG TGA
+        assert_eq!(
+            res,
+            vec![
+                Minimizer { pos: 2, kmer: encode(b"GCA")[0] },
+                Minimizer { pos: 3, kmer: encode(b"CAT")[0] },
+                Minimizer { pos: 4, kmer: encode(b"ATT")[0] },
+                Minimizer { pos: 7, kmer: encode(b"TGA")[0] },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_minimizers_canonical() {
+        let rc = NUC
+            .iter()
+            .rev()
+            .map(|n| match n {
+                b'A' => b'T',
+                b'C' => b'G',
+                b'G' => b'C',
+                _ => b'A',
+            })
+            .collect::<Vec<u8>>();
+
+        let mut forward = Minimizers::new(&encode(NUC), NUC.len(), 7, 5, Order::Canonical)
+            .map(|m| m.kmer)
+            .collect::<Vec<_>>();
+        let mut reverse = Minimizers::new(&encode(&rc), NUC.len(), 7, 5, Order::Canonical)
+            .map(|m| m.kmer)
+            .collect::<Vec<_>>();
+
+        forward.sort();
+        forward.dedup();
+        reverse.sort();
+        reverse.dedup();
+        assert_eq!(forward, reverse);
+    }
+
+    #[test]
+    fn test_minimizers_short() {
+        let bits = encode(b"ACGTA");
+
+        assert_eq!(Minimizers::new(&bits, 5, 3, 4, Order::Random).count(), 0);
+        assert_eq!(Minimizers::new(&bits, 5, 3, 3, Order::Random).count(), 1);
+    }
+}
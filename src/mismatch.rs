@@ -0,0 +1,308 @@
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use crate::utils::compact_even_bits;
+
+// one bit per nucleotide, 64 nucleotides per word, that is set where the first `len` nucleotides
+// of `a` and `b` differ
+pub fn mismatch_mask(a: &[u64], b: &[u64], len: usize) -> Vec<u64> {
+    for bits in &[a, b] {
+        if len > (bits.len() * 32) {
+            panic!(
+                "The length {} is greater than the number of nucleotides!",
+                len
+            );
+        }
+    }
+
+    if len / 64 <= 8 {
+        return mismatch_mask_scalar(a, b, len);
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { mismatch_mask_avx(a, b, len) };
+        } else if is_x86_feature_detected!("ssse3") {
+            return unsafe { mismatch_mask_sse(a, b, len) };
+        }
+    }
+
+    mismatch_mask_scalar(a, b, len)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn mismatch_mask_avx(a: &[u64], b: &[u64], len: usize) -> Vec<u64> {
+    let end_idx = (len / 32) / 8;
+
+    let mut res: Vec<u64> = Vec::with_capacity(mask_len(len));
+    let res_ptr = res.as_mut_ptr() as *mut __m256i;
+    let a_ptr = a.as_ptr() as *const __m256i;
+    let b_ptr = b.as_ptr() as *const __m256i;
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn nibbles(a: __m256i, b: __m256i) -> __m256i {
+        let xor = _mm256_xor_si256(a, b);
+        let x = _mm256_and_si256(
+            _mm256_or_si256(xor, _mm256_srli_epi64(xor, 1)),
+            _mm256_set1_epi8(0x55),
+        );
+        // the four mismatch bits of each byte are gathered in its low nibble
+        let x = _mm256_and_si256(
+            _mm256_or_si256(x, _mm256_srli_epi64(x, 1)),
+            _mm256_set1_epi8(0x33),
+        );
+        _mm256_and_si256(
+            _mm256_or_si256(x, _mm256_srli_epi64(x, 2)),
+            _mm256_set1_epi8(0x0F),
+        )
+    }
+
+    // each pair of nibbles becomes a byte
+    let pairs = _mm256_set1_epi16(0x1001);
+
+    for i in 0..end_idx {
+        let lo = nibbles(
+            _mm256_loadu_si256(a_ptr.add(i * 2)),
+            _mm256_loadu_si256(b_ptr.add(i * 2)),
+        );
+        let hi = nibbles(
+            _mm256_loadu_si256(a_ptr.add(i * 2 + 1)),
+            _mm256_loadu_si256(b_ptr.add(i * 2 + 1)),
+        );
+
+        let packed = _mm256_packus_epi16(
+            _mm256_maddubs_epi16(lo, pairs),
+            _mm256_maddubs_epi16(hi, pairs),
+        );
+        // packus works within each 128-bit lane
+        _mm256_storeu_si256(res_ptr.add(i), _mm256_permute4x64_epi64(packed, 0b11011000));
+    }
+
+    res.set_len(end_idx * 4);
+
+    let end = end_idx * 8;
+    res.extend(mismatch_mask_scalar(&a[end..], &b[end..], len - (end * 32)));
+
+    res
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+unsafe fn mismatch_mask_sse(a: &[u64], b: &[u64], len: usize) -> Vec<u64> {
+    let end_idx = (len / 32) / 4;
+
+    let mut res: Vec<u64> = Vec::with_capacity(mask_len(len));
+    let res_ptr = res.as_mut_ptr() as *mut __m128i;
+    let a_ptr = a.as_ptr() as *const __m128i;
+    let b_ptr = b.as_ptr() as *const __m128i;
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[target_feature(enable = "ssse3")]
+    #[inline]
+    unsafe fn nibbles(a: __m128i, b: __m128i) -> __m128i {
+        let xor = _mm_xor_si128(a, b);
+        let x = _mm_and_si128(
+            _mm_or_si128(xor, _mm_srli_epi64(xor, 1)),
+            _mm_set1_epi8(0x55),
+        );
+        let x = _mm_and_si128(_mm_or_si128(x, _mm_srli_epi64(x, 1)), _mm_set1_epi8(0x33));
+        _mm_and_si128(_mm_or_si128(x, _mm_srli_epi64(x, 2)), _mm_set1_epi8(0x0F))
+    }
+
+    let pairs = _mm_set1_epi16(0x1001);
+
+    for i in 0..end_idx {
+        let lo = nibbles(
+            _mm_loadu_si128(a_ptr.add(i * 2)),
+            _mm_loadu_si128(b_ptr.add(i * 2)),
+        );
+        let hi = nibbles(
+            _mm_loadu_si128(a_ptr.add(i * 2 + 1)),
+            _mm_loadu_si128(b_ptr.add(i * 2 + 1)),
+        );
+
+        let packed = _mm_packus_epi16(_mm_maddubs_epi16(lo, pairs), _mm_maddubs_epi16(hi, pairs));
+        _mm_storeu_si128(res_ptr.add(i), packed);
+    }
+
+    res.set_len(end_idx * 2);
+
+    let end = end_idx * 4;
+    res.extend(mismatch_mask_scalar(&a[end..], &b[end..], len - (end * 32)));
+
+    res
+}
+
+fn mismatch_mask_scalar(a: &[u64], b: &[u64], len: usize) -> Vec<u64> {
+    let words = (len / 32) + if len % 32 == 0 { 0 } else { 1 };
+    let mut res = vec![0u64; mask_len(len)];
+
+    let mask = 0x5555555555555555u64; // 0b...01010101
+
+    for i in 0..words {
+        let mut xor = unsafe { *a.get_unchecked(i) ^ *b.get_unchecked(i) };
+
+        if i == words - 1 && len % 32 != 0 {
+            xor &= (1 << ((len % 32) * 2)) - 1;
+        }
+
+        res[i / 2] |= compact_even_bits(((xor >> 1) | xor) & mask) << ((i % 2) * 32);
+    }
+
+    res
+}
+
+#[inline]
+fn mask_len(len: usize) -> usize {
+    (len / 64) + if len % 64 == 0 { 0 } else { 1 }
+}
+
+// the positions of the set bits of a mismatch mask, in increasing order
+pub struct Mismatches {
+    mask: Vec<u64>,
+    idx: usize,
+    word: u64,
+}
+
+impl Mismatches {
+    pub fn new(a: &[u64], b: &[u64], len: usize) -> Self {
+        Mismatches::from_mask(mismatch_mask(a, b, len))
+    }
+
+    pub fn from_mask(mask: Vec<u64>) -> Self {
+        let word = mask.first().cloned().unwrap_or(0);
+
+        Mismatches { mask, idx: 0, word }
+    }
+}
+
+impl Iterator for Mismatches {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.word == 0 {
+            self.idx += 1;
+
+            if self.idx >= self.mask.len() {
+                return None;
+            }
+
+            self.word = self.mask[self.idx];
+        }
+
+        let pos = self.idx * 64 + self.word.trailing_zeros() as usize;
+        // clear the lowest set bit
+        self.word &= self.word - 1;
+
+        Some(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nuc2bit::encode;
+    use crate::utils::random_nuc;
+
+    fn naive(a: &[u8], b: &[u8]) -> Vec<u64> {
+        let mut res = vec![0u64; mask_len(a.len())];
+
+        for (i, (x, y)) in a.iter().zip(b).enumerate() {
+            if x != y {
+                res[i / 64] |= 1 << (i % 64);
+            }
+        }
+
+        res
+    }
+
+    #[test]
+    fn test_mismatch_mask_scalar() {
+        assert_eq!(
+            mismatch_mask_scalar(&encode(b"ACGTACGT"), &encode(b"ACCTAGGA"), 8),
+            vec![0b10100100]
+        );
+
+        for &len in &[0, 1, 31, 32, 33, 64, 65, 100] {
+            let (a, b) = (random_nuc(len, 1), random_nuc(len, 2));
+            assert_eq!(
+                mismatch_mask_scalar(&encode(&a), &encode(&b), len),
+                naive(&a, &b)
+            );
+        }
+
+        // the nucleotides after `len` are ignored
+        assert_eq!(
+            mismatch_mask_scalar(&encode(b"ACGTA"), &encode(b"ACGTT"), 4),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn test_mismatch_mask_avx() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                for &len in &[0, 100, 255, 256, 257, 1000, 4190] {
+                    let (a, b) = (random_nuc(len, 1), random_nuc(len, 2));
+                    assert_eq!(
+                        unsafe { mismatch_mask_avx(&encode(&a), &encode(&b), len) },
+                        naive(&a, &b)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mismatch_mask_sse() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("ssse3") {
+                for &len in &[0, 100, 127, 128, 129, 1000, 4190] {
+                    let (a, b) = (random_nuc(len, 1), random_nuc(len, 2));
+                    assert_eq!(
+                        unsafe { mismatch_mask_sse(&encode(&a), &encode(&b), len) },
+                        naive(&a, &b)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mismatches() {
+        for &len in &[0, 10, 64, 1000] {
+            let (a, b) = (random_nuc(len, 1), random_nuc(len, 2));
+            let res = (0..len).filter(|&i| a[i] != b[i]).collect::<Vec<_>>();
+
+            assert_eq!(
+                Mismatches::new(&encode(&a), &encode(&b), len).collect::<Vec<_>>(),
+                res
+            );
+            assert_eq!(
+                res.len(),
+                crate::hamming::hamming(&encode(&a), &encode(&b), len)
+            );
+        }
+
+        assert_eq!(
+            Mismatches::from_mask(vec![0, 0b101, 0, 1 << 63]).collect::<Vec<_>>(),
+            vec![64, 66, 255]
+        );
+        assert_eq!(Mismatches::from_mask(vec![]).count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "The length 40 is greater than the number of nucleotides!")]
+    fn test_mismatch_mask_too_long() {
+        mismatch_mask(&[0; 2], &[0], 40);
+    }
+}
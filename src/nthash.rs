@@ -0,0 +1,204 @@
+use crate::bit2nuc::Codes;
+
+// seeds of ntHash in the order of the codes: A, C, T, G
+const SEEDS: [u64; 4] = [
+    0x3C8BFBB395C60474,
+    0x3193C18562A02B4C,
+    0x295549F54BE24456,
+    0x20323ED082572324,
+];
+
+const MULTI_SEED: u64 = 0x90B45D39FB6DA1FA;
+const MULTI_SHIFT: u32 = 27;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KmerHash {
+    pub forward: u64,
+    pub reverse: u64,
+    // the same for a k-mer and its reverse complement
+    pub canonical: u64,
+}
+
+impl KmerHash {
+    // fill `hashes` with more hashes of the k-mer for Bloom filters, the first one being
+    // `canonical`
+    pub fn multi(&self, k: usize, hashes: &mut [u64]) {
+        for (i, h) in hashes.iter_mut().enumerate() {
+            *h = if i == 0 {
+                self.canonical
+            } else {
+                let h = self
+                    .canonical
+                    .wrapping_mul((i as u64) ^ (k as u64).wrapping_mul(MULTI_SEED));
+                h ^ (h >> MULTI_SHIFT)
+            };
+        }
+    }
+}
+
+// rolling hashes of the k-mers of a sequence of 2-bit codes, for any k
+pub struct NtHashes<I> {
+    codes: I,
+    k: usize,
+    // the codes of the current k-mer, starting at `pos % k`
+    ring: Vec<u8>,
+    pos: usize,
+    forward: u64,
+    reverse: u64,
+}
+
+impl<I: Iterator<Item = u8>> NtHashes<I> {
+    pub fn new(codes: I, k: usize) -> Self {
+        if k == 0 {
+            panic!("The k-mer size must be at least 1!");
+        }
+
+        NtHashes {
+            codes,
+            k,
+            ring: Vec::with_capacity(k),
+            pos: 0,
+            forward: 0,
+            reverse: 0,
+        }
+    }
+}
+
+impl<'a> NtHashes<Codes<'a>> {
+    pub fn from_bits(bits: &'a [u64], len: usize, k: usize) -> Self {
+        NtHashes::new(Codes::new(bits, len), k)
+    }
+}
+
+impl<I: Iterator<Item = u8>> Iterator for NtHashes<I> {
+    type Item = KmerHash;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ring.len() < self.k {
+            while self.ring.len() < self.k {
+                self.ring.push(self.codes.next()? & 0b11);
+            }
+
+            let k = self.k as u32;
+
+            for (i, &c) in self.ring.iter().enumerate() {
+                let i = i as u32;
+                self.forward ^= SEEDS[c as usize].rotate_left(k - 1 - i);
+                // complement by flipping the high bit: A <-> T and C <-> G
+                self.reverse ^= SEEDS[(c ^ 0b10) as usize].rotate_left(i);
+            }
+        } else {
+            let c_in = self.codes.next()? & 0b11;
+            let idx = self.pos % self.k;
+            let c_out = self.ring[idx];
+            self.ring[idx] = c_in;
+            self.pos += 1;
+
+            let k = self.k as u32;
+
+            self.forward = self.forward.rotate_left(1)
+                ^ SEEDS[c_out as usize].rotate_left(k)
+                ^ SEEDS[c_in as usize];
+            self.reverse = self.reverse.rotate_right(1)
+                ^ SEEDS[(c_out ^ 0b10) as usize].rotate_right(1)
+                ^ SEEDS[(c_in ^ 0b10) as usize].rotate_left(k - 1);
+        }
+
+        Some(KmerHash {
+            forward: self.forward,
+            reverse: self.reverse,
+            canonical: self.forward.wrapping_add(self.reverse),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nuc2bit::{encode, Encode};
+
+    const NUC: &[u8] =
+        b"ACGTTGCAACGTAAGGCCTTATATGCGCACGTACGTAGGCTTAAACCCGGGTTTAGCTTGACCAGTCAGTTTTTGCAAAAAA";
+
+    fn hash(codes: &[u8]) -> KmerHash {
+        let k = codes.len() as u32;
+        let mut forward = 0;
+        let mut reverse = 0;
+
+        for (i, &c) in codes.iter().enumerate() {
+            let i = i as u32;
+            forward ^= SEEDS[c as usize].rotate_left(k - 1 - i);
+            reverse ^= SEEDS[(c ^ 0b10) as usize].rotate_left(i);
+        }
+
+        KmerHash {
+            forward,
+            reverse,
+            canonical: forward.wrapping_add(reverse),
+        }
+    }
+
+    fn reverse_complement(nuc: &[u8]) -> Vec<u8> {
+        nuc.iter()
+            .rev()
+            .map(|n| match n {
+                b'A' => b'T',
+                b'C' => b'G',
+                b'G' => b'C',
+                _ => b'A',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_nthash() {
+        let codes = Encode::new(NUC).collect::<Vec<_>>();
+        let bits = encode(NUC);
+
+        for &k in &[1, 2, 5, 31, 32, 33, 64, 65, NUC.len()] {
+            let res = codes.windows(k).map(hash).collect::<Vec<_>>();
+
+            assert_eq!(NtHashes::new(Encode::new(NUC), k).collect::<Vec<_>>(), res);
+            assert_eq!(
+                NtHashes::from_bits(&bits, NUC.len(), k).collect::<Vec<_>>(),
+                res
+            );
+        }
+
+        assert_eq!(NtHashes::new(Encode::new(NUC), NUC.len() + 1).count(), 0);
+    }
+
+    #[test]
+    fn test_nthash_canonical() {
+        let rc = reverse_complement(NUC);
+
+        for &k in &[1, 7, 32, 40] {
+            let forward = NtHashes::new(Encode::new(NUC), k).collect::<Vec<_>>();
+            let reverse = NtHashes::new(Encode::new(&rc), k).collect::<Vec<_>>();
+
+            for (f, r) in forward.iter().zip(reverse.iter().rev()) {
+                assert_eq!(f.forward, r.reverse);
+                assert_eq!(f.reverse, r.forward);
+                assert_eq!(f.canonical, r.canonical);
+            }
+        }
+    }
+
+    #[test]
+    fn test_multi() {
+        let h = NtHashes::new(Encode::new(NUC), 21).next().unwrap();
+        let mut hashes = [0; 4];
+        h.multi(21, &mut hashes);
+
+        assert_eq!(hashes[0], h.canonical);
+        for i in 1..4 {
+            for j in 0..i {
+                assert_ne!(hashes[i], hashes[j]);
+            }
+        }
+
+        let mut again = [0; 2];
+        h.multi(21, &mut again);
+        assert_eq!(again, hashes[..2]);
+    }
+}
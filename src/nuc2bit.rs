@@ -4,95 +4,483 @@ use std::arch::x86::*;
 #[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::*;
 
-use std::alloc;
+use crate::check;
+
+use std::{ascii, error, fmt};
 
 pub fn encode(nuc: &[u8]) -> Vec<u64> {
+    let mut bits = Vec::new();
+    encode_into(nuc, &mut bits);
+    bits
+}
+
+// the content of `bits` is replaced by the encoding of `nuc`, reusing its allocation
+pub fn encode_into(nuc: &[u8], bits: &mut Vec<u64>) {
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     {
         if is_x86_feature_detected!("avx2") {
-            return unsafe { encode_movemask_avx(nuc) };
+            return unsafe { encode_movemask_avx(nuc, bits) };
         } else if is_x86_feature_detected!("sse2") {
-            return unsafe { encode_movemask_sse(nuc) };
+            return unsafe { encode_movemask_sse(nuc, bits) };
         }
     }
 
-    encode_lut(nuc)
+    encode_lut(nuc, bits)
 }
 
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 #[target_feature(enable = "avx2")]
-unsafe fn encode_movemask_avx(nuc: &[u8]) -> Vec<u64> {
+unsafe fn encode_movemask_avx(nuc: &[u8], bits: &mut Vec<u64>) {
     let ptr = nuc.as_ptr() as *const __m256i;
     let end_idx = nuc.len() / 32;
     let len = end_idx + if nuc.len() % 32 == 0 { 0 } else { 1 };
 
-    let layout = alloc::Layout::from_size_align_unchecked(len * 8, 32);
-    let res_ptr = alloc::alloc(layout) as *mut u64;
+    bits.clear();
+    bits.reserve(len);
+    let res_ptr = bits.as_mut_ptr();
 
     for i in 0..end_idx as isize {
-        let v = _mm256_loadu_si256(ptr.offset(i));
+        *res_ptr.offset(i) = movemask_avx(_mm256_loadu_si256(ptr.offset(i)));
+    }
+
+    if nuc.len() % 32 > 0 {
+        *res_ptr.offset(end_idx as isize) = encode_word(&nuc[(end_idx * 32)..]);
+    }
+
+    bits.set_len(len);
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn movemask_avx(v: __m256i) -> u64 {
+    // permute because unpacks works on the low/high 64 bits in each lane
+    let v = _mm256_permute4x64_epi64(v, 0b11011000);
+
+    // shift each group of two bits for each nucleotide to the end of each byte
+    let lo = _mm256_slli_epi64(v, 6);
+    let hi = _mm256_slli_epi64(v, 5);
 
-        // permute because unpacks works on the low/high 64 bits in each lane
-        let v = _mm256_permute4x64_epi64(v, 0b11011000);
+    // interleave bytes then extract the bit at the end of each byte
+    let a = _mm256_unpackhi_epi8(lo, hi);
+    let b = _mm256_unpacklo_epi8(lo, hi);
 
-        // shift each group of two bits for each nucleotide to the end of each byte
-        let lo = _mm256_slli_epi64(v, 6);
-        let hi = _mm256_slli_epi64(v, 5);
+    // zero extend after movemask
+    let a = (_mm256_movemask_epi8(a) as u32) as u64;
+    let b = (_mm256_movemask_epi8(b) as u32) as u64;
+
+    (a << 32) | b
+}
 
-        // interleave bytes then extract the bit at the end of each byte
-        let a = _mm256_unpackhi_epi8(lo, hi);
-        let b = _mm256_unpacklo_epi8(lo, hi);
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+unsafe fn encode_movemask_sse(nuc: &[u8], bits: &mut Vec<u64>) {
+    let ptr = nuc.as_ptr() as *const __m128i;
+    let end_idx = nuc.len() / 16;
+    let len = nuc.len() / 32 + if nuc.len() % 32 == 0 { 0 } else { 1 };
 
-        // zero extend after movemask
-        let a = (_mm256_movemask_epi8(a) as u32) as u64;
-        let b = (_mm256_movemask_epi8(b) as u32) as u64;
+    bits.clear();
+    bits.reserve(len);
+    let res_ptr = bits.as_mut_ptr() as *mut u32;
 
-        *res_ptr.offset(i) = (a << 32) | b;
+    for i in 0..end_idx as isize {
+        *res_ptr.offset(i) = movemask_sse(_mm_loadu_si128(ptr.offset(i)));
     }
 
-    if nuc.len() % 32 > 0 {
-        *res_ptr.offset(end_idx as isize) = *encode_lut(&nuc[(end_idx * 32)..]).get_unchecked(0);
+    // the loop above may only fill the low half of the last word
+    if end_idx % 2 > 0 || nuc.len() % 16 > 0 {
+        let lo = if end_idx % 2 > 0 { *res_ptr.offset(end_idx as isize - 1) as u64 } else { 0 };
+        let hi = encode_word(&nuc[(end_idx * 16)..]) << ((end_idx % 2) * 32);
+
+        *(res_ptr as *mut u64).offset(len as isize - 1) = lo | hi;
     }
 
-    Vec::from_raw_parts(res_ptr, len, len)
+    bits.set_len(len);
 }
 
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 #[target_feature(enable = "sse2")]
-unsafe fn encode_movemask_sse(nuc: &[u8]) -> Vec<u64> {
+#[inline]
+unsafe fn movemask_sse(v: __m128i) -> u32 {
+    // shift each group of two bits for each nucleotide to the end of each byte
+    let lo = _mm_slli_epi64(v, 6);
+    let hi = _mm_slli_epi64(v, 5);
+
+    // interleave bytes then extract the bit at the end of each byte
+    let a = _mm_unpackhi_epi8(lo, hi);
+    let b = _mm_unpacklo_epi8(lo, hi);
+    let a = _mm_movemask_epi8(a);
+    let b = _mm_movemask_epi8(b);
+
+    ((a << 16) | b) as u32
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncodeError {
+    pub byte: u8,
+    pub pos: usize,
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid nucleotide '{}' at position {}!",
+            ascii::escape_default(self.byte),
+            self.pos
+        )
+    }
+}
+
+impl error::Error for EncodeError {}
+
+pub fn try_encode(nuc: &[u8]) -> Result<Vec<u64>, EncodeError> {
+    let mut bits = Vec::new();
+    try_encode_into(nuc, &mut bits)?;
+    Ok(bits)
+}
+
+// same as `encode_into`, but stops at the first byte that is not a nucleotide, `bits` is left
+// empty in that case
+pub fn try_encode_into(nuc: &[u8], bits: &mut Vec<u64>) -> Result<(), EncodeError> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { try_encode_movemask_avx(nuc, bits) };
+        } else if is_x86_feature_detected!("ssse3") {
+            return unsafe { try_encode_movemask_sse(nuc, bits) };
+        }
+    }
+
+    try_encode_lut(nuc, bits)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn try_encode_movemask_avx(nuc: &[u8], bits: &mut Vec<u64>) -> Result<(), EncodeError> {
+    let ptr = nuc.as_ptr() as *const __m256i;
+    let end_idx = nuc.len() / 32;
+    let len = end_idx + if nuc.len() % 32 == 0 { 0 } else { 1 };
+
+    bits.clear();
+    bits.reserve(len);
+    let res_ptr = bits.as_mut_ptr();
+
+    let lut = check::lut_avx();
+
+    for i in 0..end_idx {
+        let v = _mm256_loadu_si256(ptr.add(i));
+        let invalid = check::invalid_movemask_avx(lut, v);
+
+        if invalid != 0 {
+            let pos = i * 32 + invalid.trailing_zeros() as usize;
+            return Err(EncodeError { byte: nuc[pos], pos });
+        }
+
+        *res_ptr.add(i) = movemask_avx(v);
+    }
+
+    if nuc.len() % 32 > 0 {
+        *res_ptr.add(end_idx) = try_encode_word(&nuc[(end_idx * 32)..], end_idx * 32)?;
+    }
+
+    bits.set_len(len);
+
+    Ok(())
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+unsafe fn try_encode_movemask_sse(nuc: &[u8], bits: &mut Vec<u64>) -> Result<(), EncodeError> {
     let ptr = nuc.as_ptr() as *const __m128i;
     let end_idx = nuc.len() / 16;
     let len = nuc.len() / 32 + if nuc.len() % 32 == 0 { 0 } else { 1 };
 
-    let layout = alloc::Layout::from_size_align_unchecked(len * 8, 16);
-    let res_ptr = alloc::alloc(layout) as *mut u32;
+    bits.clear();
+    bits.reserve(len);
+    let res_ptr = bits.as_mut_ptr() as *mut u32;
 
-    for i in 0..end_idx as isize {
-        let v = _mm_loadu_si128(ptr.offset(i));
+    let lut = check::lut_sse();
+
+    for i in 0..end_idx {
+        let v = _mm_loadu_si128(ptr.add(i));
+        let invalid = check::invalid_movemask_sse(lut, v);
+
+        if invalid != 0 {
+            let pos = i * 16 + invalid.trailing_zeros() as usize;
+            return Err(EncodeError { byte: nuc[pos], pos });
+        }
+
+        *res_ptr.add(i) = movemask_sse(v);
+    }
+
+    // the loop above may only fill the low half of the last word
+    if end_idx % 2 > 0 || nuc.len() % 16 > 0 {
+        let lo = if end_idx % 2 > 0 { *res_ptr.add(end_idx - 1) as u64 } else { 0 };
+        let hi = try_encode_word(&nuc[(end_idx * 16)..], end_idx * 16)? << ((end_idx % 2) * 32);
+
+        *(res_ptr as *mut u64).add(len - 1) = lo | hi;
+    }
+
+    bits.set_len(len);
+
+    Ok(())
+}
+
+fn try_encode_lut(nuc: &[u8], bits: &mut Vec<u64>) -> Result<(), EncodeError> {
+    bits.clear();
+
+    for (i, chunk) in nuc.chunks(32).enumerate() {
+        match try_encode_word(chunk, i * 32) {
+            Ok(word) => bits.push(word),
+            Err(e) => {
+                bits.clear();
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// encode at most 32 nucleotides in one word, `start` is the position of the first one
+#[inline]
+fn try_encode_word(nuc: &[u8], start: usize) -> Result<u64, EncodeError> {
+    for i in 0..nuc.len() {
+        if unsafe { *check::CHECK_LUT.get_unchecked(*nuc.get_unchecked(i) as usize) } {
+            return Err(EncodeError { byte: nuc[i], pos: start + i });
+        }
+    }
+
+    Ok(encode_word(nuc))
+}
+
+// a run of `len` nucleotides starting at `start`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Block {
+    pub start: usize,
+    pub len: usize,
+}
+
+// `N` are encoded as `A`, the blocks keep track of where they were
+pub fn encode_with_n_blocks(nuc: &[u8]) -> (Vec<u64>, Vec<Block>) {
+    (encode(nuc), n_blocks(nuc))
+}
+
+pub fn n_blocks(nuc: &[u8]) -> Vec<Block> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { n_blocks_avx(nuc) };
+        } else if is_x86_feature_detected!("sse2") {
+            return unsafe { n_blocks_sse(nuc) };
+        }
+    }
+
+    n_blocks_scalar(nuc)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn n_blocks_avx(nuc: &[u8]) -> Vec<Block> {
+    let mut blocks = Vec::new();
+
+    let upper = _mm256_set1_epi8(b'N' as i8);
+    let lower = _mm256_set1_epi8(b'n' as i8);
+
+    for (i, chunk) in nuc.chunks(64).enumerate() {
+        let mask = if chunk.len() == 64 {
+            let ptr = chunk.as_ptr() as *const __m256i;
+            let mut mask = 0;
+
+            for j in 0..2 {
+                let v = _mm256_loadu_si256(ptr.offset(j));
+                let n = _mm256_or_si256(_mm256_cmpeq_epi8(v, upper), _mm256_cmpeq_epi8(v, lower));
+                mask |= ((_mm256_movemask_epi8(n) as u32) as u64) << (j * 32);
+            }
+
+            mask
+        } else {
+            n_mask_scalar(chunk)
+        };
+
+        push_blocks(&mut blocks, mask, i * 64);
+    }
+
+    blocks
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+unsafe fn n_blocks_sse(nuc: &[u8]) -> Vec<Block> {
+    let mut blocks = Vec::new();
+
+    let upper = _mm_set1_epi8(b'N' as i8);
+    let lower = _mm_set1_epi8(b'n' as i8);
+
+    for (i, chunk) in nuc.chunks(64).enumerate() {
+        let mask = if chunk.len() == 64 {
+            let ptr = chunk.as_ptr() as *const __m128i;
+            let mut mask = 0;
+
+            for j in 0..4 {
+                let v = _mm_loadu_si128(ptr.offset(j));
+                let n = _mm_or_si128(_mm_cmpeq_epi8(v, upper), _mm_cmpeq_epi8(v, lower));
+                mask |= ((_mm_movemask_epi8(n) as u32) as u64) << (j * 16);
+            }
+
+            mask
+        } else {
+            n_mask_scalar(chunk)
+        };
+
+        push_blocks(&mut blocks, mask, i * 64);
+    }
+
+    blocks
+}
+
+fn n_blocks_scalar(nuc: &[u8]) -> Vec<Block> {
+    let mut blocks = Vec::new();
+
+    for (i, chunk) in nuc.chunks(64).enumerate() {
+        push_blocks(&mut blocks, n_mask_scalar(chunk), i * 64);
+    }
+
+    blocks
+}
+
+// one bit per byte of at most 64 bytes, set for `N` and `n`
+fn n_mask_scalar(nuc: &[u8]) -> u64 {
+    let mut res = 0;
+
+    for (i, &n) in nuc.iter().enumerate() {
+        res |= ((n == b'N' || n == b'n') as u64) << i;
+    }
+
+    res
+}
+
+// lowercase runs are encoded as uppercase, the blocks keep track of the soft-masking
+pub fn encode_with_mask_blocks(nuc: &[u8]) -> (Vec<u64>, Vec<Block>) {
+    (encode(nuc), mask_blocks(nuc))
+}
+
+pub fn mask_blocks(nuc: &[u8]) -> Vec<Block> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { mask_blocks_avx(nuc) };
+        } else if is_x86_feature_detected!("sse2") {
+            return unsafe { mask_blocks_sse(nuc) };
+        }
+    }
+
+    mask_blocks_scalar(nuc)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn mask_blocks_avx(nuc: &[u8]) -> Vec<Block> {
+    let mut blocks = Vec::new();
+
+    // signed comparisons, bytes above 127 are negative and never lowercase
+    let before_a = _mm256_set1_epi8((b'a' - 1) as i8);
+    let after_z = _mm256_set1_epi8((b'z' + 1) as i8);
+
+    for (i, chunk) in nuc.chunks(64).enumerate() {
+        let mask = if chunk.len() == 64 {
+            let ptr = chunk.as_ptr() as *const __m256i;
+            let mut mask = 0;
+
+            for j in 0..2 {
+                let v = _mm256_loadu_si256(ptr.offset(j));
+                let lower = _mm256_and_si256(_mm256_cmpgt_epi8(v, before_a), _mm256_cmpgt_epi8(after_z, v));
+                mask |= ((_mm256_movemask_epi8(lower) as u32) as u64) << (j * 32);
+            }
+
+            mask
+        } else {
+            lower_mask_scalar(chunk)
+        };
+
+        push_blocks(&mut blocks, mask, i * 64);
+    }
+
+    blocks
+}
 
-        // shift each group of two bits for each nucleotide to the end of each byte
-        let lo = _mm_slli_epi64(v, 6);
-        let hi = _mm_slli_epi64(v, 5);
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+unsafe fn mask_blocks_sse(nuc: &[u8]) -> Vec<Block> {
+    let mut blocks = Vec::new();
+
+    let before_a = _mm_set1_epi8((b'a' - 1) as i8);
+    let after_z = _mm_set1_epi8((b'z' + 1) as i8);
+
+    for (i, chunk) in nuc.chunks(64).enumerate() {
+        let mask = if chunk.len() == 64 {
+            let ptr = chunk.as_ptr() as *const __m128i;
+            let mut mask = 0;
+
+            for j in 0..4 {
+                let v = _mm_loadu_si128(ptr.offset(j));
+                let lower = _mm_and_si128(_mm_cmpgt_epi8(v, before_a), _mm_cmplt_epi8(v, after_z));
+                mask |= ((_mm_movemask_epi8(lower) as u32) as u64) << (j * 16);
+            }
+
+            mask
+        } else {
+            lower_mask_scalar(chunk)
+        };
+
+        push_blocks(&mut blocks, mask, i * 64);
+    }
+
+    blocks
+}
 
-        // interleave bytes then extract the bit at the end of each byte
-        let a = _mm_unpackhi_epi8(lo, hi);
-        let b = _mm_unpacklo_epi8(lo, hi);
-        let a = _mm_movemask_epi8(a);
-        let b = _mm_movemask_epi8(b);
+fn mask_blocks_scalar(nuc: &[u8]) -> Vec<Block> {
+    let mut blocks = Vec::new();
 
-        *res_ptr.offset(i) = ((a << 16) | b) as u32;
+    for (i, chunk) in nuc.chunks(64).enumerate() {
+        push_blocks(&mut blocks, lower_mask_scalar(chunk), i * 64);
     }
 
-    if nuc.len() % 16 > 0 {
-        *res_ptr.offset(end_idx as isize) =
-            *encode_lut(&nuc[(end_idx * 16)..]).get_unchecked(0) as u32;
+    blocks
+}
+
+fn lower_mask_scalar(nuc: &[u8]) -> u64 {
+    let mut res = 0;
+
+    for (i, &n) in nuc.iter().enumerate() {
+        res |= (n.is_ascii_lowercase() as u64) << i;
     }
 
-    Vec::from_raw_parts(res_ptr as *mut u64, len, len)
+    res
+}
+
+// add the runs of set bits of `mask` to `blocks`, the bit 0 of `mask` is at position `pos`, runs
+// that continue the last block extend it
+fn push_blocks(blocks: &mut Vec<Block>, mut mask: u64, pos: usize) {
+    while mask != 0 {
+        let start = mask.trailing_zeros() as usize;
+        let len = (!(mask >> start)).trailing_zeros() as usize;
+
+        match blocks.last_mut() {
+            Some(last) if last.start + last.len == pos + start => last.len += len,
+            _ => blocks.push(Block {
+                start: pos + start,
+                len,
+            }),
+        }
+
+        mask = if start + len >= 64 { 0 } else { mask & (!0u64 << (start + len)) };
+    }
 }
 
-static BYTE_LUT: [u8; 128] = {
-    let mut lut = [0u8; 128];
+static BYTE_LUT: [u8; 256] = {
+    let mut lut = [0u8; 256];
     lut[b'a' as usize] = 0b00;
     lut[b't' as usize] = 0b10;
     lut[b'u' as usize] = 0b10;
@@ -106,16 +494,19 @@ static BYTE_LUT: [u8; 128] = {
     lut
 };
 
-fn encode_lut(nuc: &[u8]) -> Vec<u64> {
-    let mut res = vec![0u64; (nuc.len() / 32) + if nuc.len() % 32 == 0 { 0 } else { 1 }];
+fn encode_lut(nuc: &[u8], bits: &mut Vec<u64>) {
+    bits.clear();
+    bits.extend(nuc.chunks(32).map(encode_word));
+}
 
-    for i in 0..nuc.len() {
-        let offset = i / 32;
-        let shift = (i % 32) << 1;
+// encode at most 32 nucleotides in one word
+#[inline]
+fn encode_word(nuc: &[u8]) -> u64 {
+    let mut res = 0;
 
+    for i in 0..nuc.len() {
         unsafe {
-            *res.get_unchecked_mut(offset) = *res.get_unchecked(offset)
-                | ((*BYTE_LUT.get_unchecked(*nuc.get_unchecked(i) as usize) as u64) << shift);
+            res |= (*BYTE_LUT.get_unchecked(*nuc.get_unchecked(i) as usize) as u64) << (i << 1);
         }
     }
 
@@ -124,19 +515,48 @@ fn encode_lut(nuc: &[u8]) -> Vec<u64> {
 
 #[cfg(feature = "bench-internals")]
 pub fn pub_encode_lut(nuc: &[u8]) -> Vec<u64> {
-    encode_lut(nuc)
+    let mut bits = Vec::new();
+    encode_lut(nuc, &mut bits);
+    bits
 }
 
 #[cfg(feature = "bench-internals")]
 #[target_feature(enable = "avx2")]
 pub unsafe fn pub_encode_avx(nuc: &[u8]) -> Vec<u64> {
-    encode_movemask_avx(nuc)
+    let mut bits = Vec::new();
+    encode_movemask_avx(nuc, &mut bits);
+    bits
 }
 
 #[cfg(feature = "bench-internals")]
 #[target_feature(enable = "sse2")]
 pub unsafe fn pub_encode_sse(nuc: &[u8]) -> Vec<u64> {
-    encode_movemask_sse(nuc)
+    let mut bits = Vec::new();
+    encode_movemask_sse(nuc, &mut bits);
+    bits
+}
+
+#[cfg(feature = "bench-internals")]
+pub fn pub_try_encode_lut(nuc: &[u8]) -> Result<Vec<u64>, EncodeError> {
+    let mut bits = Vec::new();
+    try_encode_lut(nuc, &mut bits)?;
+    Ok(bits)
+}
+
+#[cfg(feature = "bench-internals")]
+#[target_feature(enable = "avx2")]
+pub unsafe fn pub_try_encode_avx(nuc: &[u8]) -> Result<Vec<u64>, EncodeError> {
+    let mut bits = Vec::new();
+    try_encode_movemask_avx(nuc, &mut bits)?;
+    Ok(bits)
+}
+
+#[cfg(feature = "bench-internals")]
+#[target_feature(enable = "ssse3")]
+pub unsafe fn pub_try_encode_sse(nuc: &[u8]) -> Result<Vec<u64>, EncodeError> {
+    let mut bits = Vec::new();
+    try_encode_movemask_sse(nuc, &mut bits)?;
+    Ok(bits)
 }
 
 pub struct Encode<'a> {
@@ -169,14 +589,34 @@ mod tests {
     use super::*;
     use crate::utils::encoding_equals;
 
+    fn encode_with_lut(nuc: &[u8]) -> Vec<u64> {
+        let mut bits = Vec::new();
+        encode_lut(nuc, &mut bits);
+        bits
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    unsafe fn encode_with_avx(nuc: &[u8]) -> Vec<u64> {
+        let mut bits = Vec::new();
+        encode_movemask_avx(nuc, &mut bits);
+        bits
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    unsafe fn encode_with_sse(nuc: &[u8]) -> Vec<u64> {
+        let mut bits = Vec::new();
+        encode_movemask_sse(nuc, &mut bits);
+        bits
+    }
+
     #[test]
     fn test_encode_lut() {
         assert!(encoding_equals(
-            &encode_lut(b"AUCGATCGATCGATCGATCGATCGATCGATCG"),
+            &encode_with_lut(b"AUCGATCGATCGATCGATCGATCGATCGATCG"),
             &vec![0b1101100011011000110110001101100011011000110110001101100011011000],
             32
         ));
-        assert!(encoding_equals(&encode_lut(b"ATCG"), &vec![0b11011000], 4));
+        assert!(encoding_equals(&encode_with_lut(b"ATCG"), &vec![0b11011000], 4));
     }
 
     #[test]
@@ -187,16 +627,16 @@ mod tests {
                 unsafe {
                     println!(
                         "{:?}",
-                        encode_movemask_avx(b"AUCGATCGATCGATCGATCGATCGATCGATCGAAA")
+                        encode_with_avx(b"AUCGATCGATCGATCGATCGATCGATCGATCGAAA")
                     )
                 };
                 assert!(encoding_equals(
-                    &unsafe { encode_movemask_avx(b"AUCGATCGATCGATCGATCGATCGATCGATCG") },
+                    &unsafe { encode_with_avx(b"AUCGATCGATCGATCGATCGATCGATCGATCG") },
                     &vec![0b1101100011011000110110001101100011011000110110001101100011011000],
                     32
                 ));
                 assert!(encoding_equals(
-                    &unsafe { encode_movemask_avx(b"ATCG") },
+                    &unsafe { encode_with_avx(b"ATCG") },
                     &vec![0b11011000],
                     4
                 ));
@@ -210,12 +650,12 @@ mod tests {
         {
             if is_x86_feature_detected!("sse2") {
                 assert!(encoding_equals(
-                    &unsafe { encode_movemask_sse(b"AUCGATCGATCGATCGATCGATCGATCGATCG") },
+                    &unsafe { encode_with_sse(b"AUCGATCGATCGATCGATCGATCGATCGATCG") },
                     &vec![0b1101100011011000110110001101100011011000110110001101100011011000],
                     32
                 ));
                 assert!(encoding_equals(
-                    &unsafe { encode_movemask_sse(b"ATCG") },
+                    &unsafe { encode_with_sse(b"ATCG") },
                     &vec![0b11011000],
                     4
                 ));
@@ -223,6 +663,247 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_encode_into() {
+        let mut bits = vec![0xFFFFFFFFFFFFFFFF; 3];
+
+        encode_into(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCG", &mut bits);
+        assert_eq!(
+            bits,
+            vec![0b1101100011011000110110001101100011011000110110001101100011011000, 0b11011000110110001101100011011000]
+        );
+
+        encode_into(b"ATCG", &mut bits);
+        assert_eq!(bits, vec![0b11011000]);
+
+        encode_into(b"", &mut bits);
+        assert!(bits.is_empty());
+
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            // every partial last word
+            let nuc = b"GGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGG";
+
+            for len in 0..=64 {
+                let expected = encode_with_lut(&nuc[..len]);
+
+                if is_x86_feature_detected!("avx2") {
+                    assert_eq!(unsafe { encode_with_avx(&nuc[..len]) }, expected);
+                }
+
+                if is_x86_feature_detected!("sse2") {
+                    assert_eq!(unsafe { encode_with_sse(&nuc[..len]) }, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_encode_lut() {
+        let mut bits = Vec::new();
+
+        assert_eq!(try_encode_lut(b"AUCGATCGATCGATCGATCGATCGATCGATCGatcg", &mut bits), Ok(()));
+        assert_eq!(bits, encode(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG"));
+
+        assert_eq!(
+            try_encode_lut(b"AUCGATCGATCGATCGATCGATCGATCGATCGATNG", &mut bits),
+            Err(EncodeError { byte: b'N', pos: 34 })
+        );
+        assert!(bits.is_empty());
+        assert_eq!(
+            try_encode_lut(b"\xC1TCG", &mut bits),
+            Err(EncodeError { byte: 0xC1, pos: 0 })
+        );
+    }
+
+    #[test]
+    fn test_try_encode_avx() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                let mut bits = Vec::new();
+
+                assert_eq!(
+                    unsafe { try_encode_movemask_avx(b"AUCGATCGATCGATCGATCGATCGATCGATCGatcg", &mut bits) },
+                    Ok(())
+                );
+                assert_eq!(bits, encode(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG"));
+
+                assert_eq!(
+                    unsafe { try_encode_movemask_avx(b"AUCGATCGATCGATCGATCGATCGATCGATCGATNG", &mut bits) },
+                    Err(EncodeError { byte: b'N', pos: 34 })
+                );
+                assert!(bits.is_empty());
+                assert_eq!(
+                    unsafe { try_encode_movemask_avx(b"AUCGATCGATCGATCGATCG ATCGATCGATCGATCG", &mut bits) },
+                    Err(EncodeError { byte: b' ', pos: 20 })
+                );
+                assert_eq!(
+                    unsafe { try_encode_movemask_avx(b"AUCGATCGATCGATCGATCGATCGATCGAT\xC1G", &mut bits) },
+                    Err(EncodeError { byte: 0xC1, pos: 30 })
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_encode_sse() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("ssse3") {
+                let mut bits = Vec::new();
+
+                assert_eq!(
+                    unsafe { try_encode_movemask_sse(b"AUCGATCGATCGATCGATCGATCGATCGATCGatcg", &mut bits) },
+                    Ok(())
+                );
+                assert_eq!(bits, encode(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG"));
+
+                assert_eq!(
+                    unsafe { try_encode_movemask_sse(b"AUCGATCGATCGATCGATCGATCGATCGATCGATNG", &mut bits) },
+                    Err(EncodeError { byte: b'N', pos: 34 })
+                );
+                assert!(bits.is_empty());
+                assert_eq!(
+                    unsafe { try_encode_movemask_sse(b"AUCGATCGATCGATCGATCG ATCGATCGATCGATCG", &mut bits) },
+                    Err(EncodeError { byte: b' ', pos: 20 })
+                );
+                assert_eq!(
+                    unsafe { try_encode_movemask_sse(b"AUCGATCGATCGATCGATCGATCGATCGAT\xC1G", &mut bits) },
+                    Err(EncodeError { byte: 0xC1, pos: 30 })
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_encode() {
+        assert_eq!(try_encode(b"ATCG"), Ok(vec![0b11011000]));
+        assert_eq!(try_encode(b""), Ok(vec![]));
+        assert_eq!(
+            try_encode(b"ATCG-").unwrap_err().to_string(),
+            "Invalid nucleotide '-' at position 4!"
+        );
+        assert_eq!(
+            try_encode(b"AT\nCG").unwrap_err().to_string(),
+            "Invalid nucleotide '\\n' at position 2!"
+        );
+    }
+
+    #[test]
+    fn test_push_blocks() {
+        let mut blocks = Vec::new();
+
+        push_blocks(&mut blocks, 0b0110_0111, 0);
+        assert_eq!(blocks, vec![Block { start: 0, len: 3 }, Block { start: 5, len: 2 }]);
+
+        push_blocks(&mut blocks, 0xFFFFFFFFFFFFFFFF, 64);
+        push_blocks(&mut blocks, 0x8000000000000001, 128);
+        assert_eq!(
+            blocks,
+            vec![
+                Block { start: 0, len: 3 },
+                Block { start: 5, len: 2 },
+                Block { start: 64, len: 65 },
+                Block { start: 191, len: 1 }
+            ]
+        );
+    }
+
+    const WITH_N: &[u8] = b"NNACGTNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNACGTnnnnACGTN";
+
+    fn with_n_blocks() -> Vec<Block> {
+        vec![
+            Block { start: 0, len: 2 },
+            Block { start: 6, len: 64 },
+            Block { start: 74, len: 4 },
+            Block { start: 82, len: 1 },
+        ]
+    }
+
+    #[test]
+    fn test_n_blocks_scalar() {
+        assert_eq!(n_blocks_scalar(WITH_N), with_n_blocks());
+        assert_eq!(n_blocks_scalar(b"ACGT"), vec![]);
+    }
+
+    #[test]
+    fn test_n_blocks_avx() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                assert_eq!(unsafe { n_blocks_avx(WITH_N) }, with_n_blocks());
+                assert_eq!(unsafe { n_blocks_avx(b"ACGT") }, vec![]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_n_blocks_sse() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("sse2") {
+                assert_eq!(unsafe { n_blocks_sse(WITH_N) }, with_n_blocks());
+                assert_eq!(unsafe { n_blocks_sse(b"ACGT") }, vec![]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_with_n_blocks() {
+        let (bits, blocks) = encode_with_n_blocks(b"ANNTCGNN");
+
+        assert_eq!(bits, encode(b"AAATCGAA"));
+        assert_eq!(blocks, vec![Block { start: 1, len: 2 }, Block { start: 6, len: 2 }]);
+    }
+
+    const SOFT_MASKED: &[u8] = b"acGTACGTACGTACGTACGTACGTACGTACGTACGTACGTACGTACGTACGTACGTACgtacgtacgtAC{GT`nnACGTa";
+
+    fn soft_masked_blocks() -> Vec<Block> {
+        vec![
+            Block { start: 0, len: 2 },
+            Block { start: 58, len: 10 },
+            Block { start: 74, len: 2 },
+            Block { start: 80, len: 1 },
+        ]
+    }
+
+    #[test]
+    fn test_mask_blocks_scalar() {
+        assert_eq!(mask_blocks_scalar(SOFT_MASKED), soft_masked_blocks());
+        assert_eq!(mask_blocks_scalar(b"ACGT"), vec![]);
+    }
+
+    #[test]
+    fn test_mask_blocks_avx() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                assert_eq!(unsafe { mask_blocks_avx(SOFT_MASKED) }, soft_masked_blocks());
+                assert_eq!(unsafe { mask_blocks_avx(b"ACGT") }, vec![]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mask_blocks_sse() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("sse2") {
+                assert_eq!(unsafe { mask_blocks_sse(SOFT_MASKED) }, soft_masked_blocks());
+                assert_eq!(unsafe { mask_blocks_sse(b"ACGT") }, vec![]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_with_mask_blocks() {
+        let (bits, blocks) = encode_with_mask_blocks(b"AcgTCGta");
+
+        assert_eq!(bits, encode(b"ACGTCGTA"));
+        assert_eq!(blocks, vec![Block { start: 1, len: 2 }, Block { start: 6, len: 2 }]);
+    }
+
     #[test]
     fn test_encode_iterator() {
         let mut bits = Vec::new();
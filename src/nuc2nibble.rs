@@ -0,0 +1,615 @@
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+// one bit per base: A=0001, C=0010, G=0100, T=1000, ambiguity codes are the union of their bases
+// and anything that is not a nucleotide is a gap (0000), 16 nucleotides per word with the first
+// one in the low bits
+pub fn encode(nuc: &[u8]) -> Vec<u64> {
+    let mut nibbles = Vec::new();
+    encode_into(nuc, &mut nibbles);
+    nibbles
+}
+
+pub fn encode_into(nuc: &[u8], nibbles: &mut Vec<u64>) {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { encode_shuffle_avx(nuc, nibbles) };
+        } else if is_x86_feature_detected!("ssse3") {
+            return unsafe { encode_shuffle_sse(nuc, nibbles) };
+        }
+    }
+
+    encode_lut(nuc, nibbles)
+}
+
+// codes of the bytes 0x40 to 0x4F and 0x50 to 0x5F, lowercase letters use the same rows
+const ROW_4: [u8; 16] = [0, 1, 14, 2, 13, 0, 0, 4, 11, 0, 0, 12, 0, 3, 15, 0];
+const ROW_5: [u8; 16] = [0, 0, 5, 6, 8, 8, 7, 9, 15, 10, 0, 0, 0, 0, 0, 0];
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn encode_shuffle_avx(nuc: &[u8], nibbles: &mut Vec<u64>) {
+    let ptr = nuc.as_ptr() as *const __m256i;
+    let end_idx = nuc.len() / 64;
+    let len = nuc.len() / 16 + if nuc.len() % 16 == 0 { 0 } else { 1 };
+
+    nibbles.clear();
+    nibbles.reserve(len);
+    let res_ptr = nibbles.as_mut_ptr() as *mut __m256i;
+
+    let row_4 = _mm256_broadcastsi128_si256(_mm_loadu_si128(ROW_4.as_ptr() as *const __m128i));
+    let row_5 = _mm256_broadcastsi128_si256(_mm_loadu_si128(ROW_5.as_ptr() as *const __m128i));
+    let pack = _mm256_set1_epi16(0x1001);
+
+    for i in 0..end_idx as isize {
+        let a = codes_avx(row_4, row_5, _mm256_loadu_si256(ptr.offset(i * 2)));
+        let b = codes_avx(row_4, row_5, _mm256_loadu_si256(ptr.offset(i * 2 + 1)));
+
+        // two codes per byte, then undo the interleaving of the lanes by packus
+        let v = _mm256_packus_epi16(_mm256_maddubs_epi16(a, pack), _mm256_maddubs_epi16(b, pack));
+        _mm256_storeu_si256(res_ptr.offset(i), _mm256_permute4x64_epi64(v, 0b11011000));
+    }
+
+    let res_ptr = nibbles.as_mut_ptr();
+
+    for (i, chunk) in nuc[(end_idx * 64)..].chunks(16).enumerate() {
+        *res_ptr.offset((end_idx * 4 + i) as isize) = encode_word(chunk);
+    }
+
+    nibbles.set_len(len);
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn codes_avx(row_4: __m256i, row_5: __m256i, v: __m256i) -> __m256i {
+    let idx = _mm256_and_si256(v, _mm256_set1_epi8(0x0F));
+    let row = _mm256_cmpeq_epi8(
+        _mm256_and_si256(v, _mm256_set1_epi8(0x10)),
+        _mm256_set1_epi8(0x10),
+    );
+    let codes = _mm256_blendv_epi8(
+        _mm256_shuffle_epi8(row_4, idx),
+        _mm256_shuffle_epi8(row_5, idx),
+        row,
+    );
+
+    // only 0x40 to 0x7F can be letters
+    let letter = _mm256_cmpeq_epi8(
+        _mm256_and_si256(v, _mm256_set1_epi8(0xC0u8 as i8)),
+        _mm256_set1_epi8(0x40),
+    );
+    _mm256_and_si256(codes, letter)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+unsafe fn encode_shuffle_sse(nuc: &[u8], nibbles: &mut Vec<u64>) {
+    let ptr = nuc.as_ptr() as *const __m128i;
+    let end_idx = nuc.len() / 32;
+    let len = nuc.len() / 16 + if nuc.len() % 16 == 0 { 0 } else { 1 };
+
+    nibbles.clear();
+    nibbles.reserve(len);
+    let res_ptr = nibbles.as_mut_ptr() as *mut __m128i;
+
+    let row_4 = _mm_loadu_si128(ROW_4.as_ptr() as *const __m128i);
+    let row_5 = _mm_loadu_si128(ROW_5.as_ptr() as *const __m128i);
+    let pack = _mm_set1_epi16(0x1001);
+
+    for i in 0..end_idx as isize {
+        let a = codes_sse(row_4, row_5, _mm_loadu_si128(ptr.offset(i * 2)));
+        let b = codes_sse(row_4, row_5, _mm_loadu_si128(ptr.offset(i * 2 + 1)));
+
+        // two codes per byte
+        let v = _mm_packus_epi16(_mm_maddubs_epi16(a, pack), _mm_maddubs_epi16(b, pack));
+        _mm_storeu_si128(res_ptr.offset(i), v);
+    }
+
+    let res_ptr = nibbles.as_mut_ptr();
+
+    for (i, chunk) in nuc[(end_idx * 32)..].chunks(16).enumerate() {
+        *res_ptr.offset((end_idx * 2 + i) as isize) = encode_word(chunk);
+    }
+
+    nibbles.set_len(len);
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+#[inline]
+unsafe fn codes_sse(row_4: __m128i, row_5: __m128i, v: __m128i) -> __m128i {
+    let idx = _mm_and_si128(v, _mm_set1_epi8(0x0F));
+    let row = _mm_cmpeq_epi8(_mm_and_si128(v, _mm_set1_epi8(0x10)), _mm_set1_epi8(0x10));
+    let codes = _mm_or_si128(
+        _mm_andnot_si128(row, _mm_shuffle_epi8(row_4, idx)),
+        _mm_and_si128(row, _mm_shuffle_epi8(row_5, idx)),
+    );
+
+    // only 0x40 to 0x7F can be letters
+    let letter = _mm_cmpeq_epi8(
+        _mm_and_si128(v, _mm_set1_epi8(0xC0u8 as i8)),
+        _mm_set1_epi8(0x40),
+    );
+    _mm_and_si128(codes, letter)
+}
+
+fn encode_lut(nuc: &[u8], nibbles: &mut Vec<u64>) {
+    nibbles.clear();
+    nibbles.extend(nuc.chunks(16).map(encode_word));
+}
+
+// encode at most 16 nucleotides in one word
+#[inline]
+fn encode_word(nuc: &[u8]) -> u64 {
+    let mut res = 0;
+
+    for (i, &n) in nuc.iter().enumerate() {
+        res |= (code(n) as u64) << (i << 2);
+    }
+
+    res
+}
+
+#[inline]
+fn code(n: u8) -> u8 {
+    let row = if n & 0x10 == 0 { &ROW_4 } else { &ROW_5 };
+
+    if n & 0xC0 == 0x40 {
+        row[(n & 0x0F) as usize]
+    } else {
+        0
+    }
+}
+
+const NUC_LUT: [u8; 16] = *b"-ACMGRSVTWYHKDBN";
+
+pub fn decode(nibbles: &[u64], len: usize) -> Vec<u8> {
+    let mut nuc = Vec::new();
+    decode_into(nibbles, len, &mut nuc);
+    nuc
+}
+
+pub fn decode_into(nibbles: &[u64], len: usize, nuc: &mut Vec<u8>) {
+    if len > (nibbles.len() * 16) {
+        panic!(
+            "The length {} is greater than the number of nucleotides!",
+            len
+        );
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { decode_shuffle_avx(nibbles, len, nuc) };
+        } else if is_x86_feature_detected!("ssse3") {
+            return unsafe { decode_shuffle_sse(nibbles, len, nuc) };
+        }
+    }
+
+    decode_lut(nibbles, len, nuc)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn decode_shuffle_avx(nibbles: &[u64], len: usize, nuc: &mut Vec<u8>) {
+    let words = (len / 16) + if len % 16 == 0 { 0 } else { 1 };
+    let end_idx = words / 4;
+
+    // whole words are decoded, so there may be up to 15 extra bytes after the end
+    nuc.clear();
+    nuc.reserve(words * 16);
+
+    let ptr = nibbles.as_ptr() as *const __m256i;
+    let res_ptr = nuc.as_mut_ptr() as *mut __m256i;
+
+    let lut = _mm256_broadcastsi128_si256(_mm_loadu_si128(NUC_LUT.as_ptr() as *const __m128i));
+    let lo_mask = _mm256_set1_epi8(0x0F);
+
+    for i in 0..end_idx as isize {
+        let v = _mm256_loadu_si256(ptr.offset(i));
+        let lo = _mm256_and_si256(v, lo_mask);
+        let hi = _mm256_and_si256(_mm256_srli_epi16(v, 4), lo_mask);
+
+        // the unpacks work in each lane, so the halves of the lanes are put back in order
+        let a = _mm256_unpacklo_epi8(lo, hi);
+        let b = _mm256_unpackhi_epi8(lo, hi);
+        let first = _mm256_permute2x128_si256(a, b, 0x20);
+        let second = _mm256_permute2x128_si256(a, b, 0x31);
+
+        _mm256_storeu_si256(res_ptr.offset(i * 2), _mm256_shuffle_epi8(lut, first));
+        _mm256_storeu_si256(res_ptr.offset(i * 2 + 1), _mm256_shuffle_epi8(lut, second));
+    }
+
+    let res_ptr = nuc.as_mut_ptr();
+
+    for (i, &w) in nibbles[..words].iter().enumerate().skip(end_idx * 4) {
+        decode_word(w, res_ptr.add(i * 16));
+    }
+
+    nuc.set_len(len);
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+unsafe fn decode_shuffle_sse(nibbles: &[u64], len: usize, nuc: &mut Vec<u8>) {
+    let words = (len / 16) + if len % 16 == 0 { 0 } else { 1 };
+    let end_idx = words / 2;
+
+    // whole words are decoded, so there may be up to 15 extra bytes after the end
+    nuc.clear();
+    nuc.reserve(words * 16);
+
+    let ptr = nibbles.as_ptr() as *const __m128i;
+    let res_ptr = nuc.as_mut_ptr() as *mut __m128i;
+
+    let lut = _mm_loadu_si128(NUC_LUT.as_ptr() as *const __m128i);
+    let lo_mask = _mm_set1_epi8(0x0F);
+
+    for i in 0..end_idx as isize {
+        let v = _mm_loadu_si128(ptr.offset(i));
+        let lo = _mm_and_si128(v, lo_mask);
+        let hi = _mm_and_si128(_mm_srli_epi16(v, 4), lo_mask);
+
+        _mm_storeu_si128(
+            res_ptr.offset(i * 2),
+            _mm_shuffle_epi8(lut, _mm_unpacklo_epi8(lo, hi)),
+        );
+        _mm_storeu_si128(
+            res_ptr.offset(i * 2 + 1),
+            _mm_shuffle_epi8(lut, _mm_unpackhi_epi8(lo, hi)),
+        );
+    }
+
+    let res_ptr = nuc.as_mut_ptr();
+
+    for (i, &w) in nibbles[..words].iter().enumerate().skip(end_idx * 2) {
+        decode_word(w, res_ptr.add(i * 16));
+    }
+
+    nuc.set_len(len);
+}
+
+fn decode_lut(nibbles: &[u64], len: usize, nuc: &mut Vec<u8>) {
+    let words = (len / 16) + if len % 16 == 0 { 0 } else { 1 };
+
+    nuc.clear();
+    nuc.reserve(words * 16);
+
+    unsafe {
+        let res_ptr = nuc.as_mut_ptr();
+
+        for (i, &w) in nibbles[..words].iter().enumerate() {
+            decode_word(w, res_ptr.add(i * 16));
+        }
+
+        nuc.set_len(len);
+    }
+}
+
+// write the 16 nucleotides of a word
+#[inline]
+unsafe fn decode_word(w: u64, res_ptr: *mut u8) {
+    for j in 0..16 {
+        *res_ptr.add(j) = NUC_LUT[((w >> (j << 2)) & 0b1111) as usize];
+    }
+}
+
+// reversing the bits of each nibble swaps A with T and C with G
+pub fn complement(nibbles: &[u64]) -> Vec<u64> {
+    nibbles.iter().map(|&w| complement_word(w)).collect()
+}
+
+pub fn complement_in_place(nibbles: &mut [u64]) {
+    for w in nibbles.iter_mut() {
+        *w = complement_word(*w);
+    }
+}
+
+#[inline]
+fn complement_word(w: u64) -> u64 {
+    let w = ((w >> 1) & 0x5555555555555555) | ((w & 0x5555555555555555) << 1);
+    ((w >> 2) & 0x3333333333333333) | ((w & 0x3333333333333333) << 2)
+}
+
+// the 2-bit encoding of the first `len` nucleotides, or `None` if one of them is ambiguous or a gap
+pub fn to_bits(nibbles: &[u64], len: usize) -> Option<Vec<u64>> {
+    if len > (nibbles.len() * 16) {
+        panic!(
+            "The length {} is greater than the number of nucleotides!",
+            len
+        );
+    }
+
+    let words = (len / 16) + if len % 16 == 0 { 0 } else { 1 };
+    let mut bits = Vec::with_capacity(words / 2 + 1);
+
+    for (i, pair) in nibbles[..words].chunks(2).enumerate() {
+        let mut res = 0;
+
+        for (j, &w) in pair.iter().enumerate() {
+            // only the nibbles in the sequence are checked
+            let used = len - (i * 32 + j * 16);
+            let mask = if used >= 16 {
+                0x1111111111111111
+            } else {
+                0x1111111111111111 >> ((16 - used) * 4)
+            };
+
+            let a = w & 0x1111111111111111;
+            let c = (w >> 1) & 0x1111111111111111;
+            let g = (w >> 2) & 0x1111111111111111;
+            let t = (w >> 3) & 0x1111111111111111;
+
+            // exactly one bit in each nibble
+            if (a + c + g + t) & (mask * 0b1111) != mask {
+                return None;
+            }
+
+            let code = ((c | g) | ((g | t) << 1)) & (mask * 0b11);
+            res |= compact_nibbles(code) << (j * 32);
+        }
+
+        bits.push(res);
+    }
+
+    Some(bits)
+}
+
+// the 4-bit encoding of `len` nucleotides in 2-bit encoding, which is always lossless
+pub fn from_bits(bits: &[u64], len: usize) -> Vec<u64> {
+    if len > (bits.len() * 32) {
+        panic!(
+            "The length {} is greater than the number of nucleotides!",
+            len
+        );
+    }
+
+    let words = (len / 16) + if len % 16 == 0 { 0 } else { 1 };
+    let mut nibbles = Vec::with_capacity(words);
+
+    for i in 0..words {
+        let used = len - i * 16;
+        let mask = if used >= 16 {
+            0x1111111111111111
+        } else {
+            0x1111111111111111 >> ((16 - used) * 4)
+        };
+
+        let code = spread_nibbles((bits[i / 2] >> ((i % 2) * 32)) as u32);
+        let lo = code & 0x1111111111111111;
+        let hi = (code >> 1) & 0x1111111111111111;
+
+        // A=00, C=01, T=10, G=11
+        let a = !lo & !hi & mask;
+        let c = lo & !hi & mask;
+        let g = lo & hi & mask;
+        let t = !lo & hi & mask;
+
+        nibbles.push(a | (c << 1) | (g << 2) | (t << 3));
+    }
+
+    nibbles
+}
+
+// move the 2 low bits of each nibble next to each other
+#[inline]
+fn compact_nibbles(w: u64) -> u64 {
+    let w = (w | (w >> 2)) & 0x0F0F0F0F0F0F0F0F;
+    let w = (w | (w >> 4)) & 0x00FF00FF00FF00FF;
+    let w = (w | (w >> 8)) & 0x0000FFFF0000FFFF;
+    (w | (w >> 16)) & 0x00000000FFFFFFFF
+}
+
+#[inline]
+fn spread_nibbles(w: u32) -> u64 {
+    let w = w as u64;
+    let w = (w | (w << 16)) & 0x0000FFFF0000FFFF;
+    let w = (w | (w << 8)) & 0x00FF00FF00FF00FF;
+    let w = (w | (w << 4)) & 0x0F0F0F0F0F0F0F0F;
+    (w | (w << 2)) & 0x3333333333333333
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IUPAC: &[u8] = b"ACGTUacgturykmswbdhvnRYKMSWBDHVN-xXZ.*@[`{\x80\xC1\xFFEIJLOPQ";
+
+    fn iupac_nibbles() -> Vec<u8> {
+        let mut res = vec![
+            1, 2, 4, 8, 8, 1, 2, 4, 8, 8, 5, 10, 12, 3, 6, 9, 14, 13, 11, 7, 15,
+        ];
+        res.extend_from_slice(&[5, 10, 12, 3, 6, 9, 14, 13, 11, 7, 15, 0, 15, 15]);
+        res.extend_from_slice(&[0; 17]);
+        res
+    }
+
+    fn nibbles(codes: &[u8]) -> Vec<u64> {
+        codes
+            .chunks(16)
+            .map(|c| {
+                c.iter()
+                    .enumerate()
+                    .fold(0, |acc, (i, &n)| acc | ((n as u64) << (i * 4)))
+            })
+            .collect()
+    }
+
+    fn long_iupac() -> (Vec<u8>, Vec<u64>) {
+        let nuc = IUPAC.repeat(7);
+        let codes = iupac_nibbles().repeat(7);
+        (nuc, nibbles(&codes))
+    }
+
+    fn encode_with(f: unsafe fn(&[u8], &mut Vec<u64>), nuc: &[u8]) -> Vec<u64> {
+        let mut res = vec![0xFFFF];
+        unsafe { f(nuc, &mut res) };
+        res
+    }
+
+    fn decode_with(
+        f: unsafe fn(&[u64], usize, &mut Vec<u8>),
+        nibbles: &[u64],
+        len: usize,
+    ) -> Vec<u8> {
+        let mut res = b"ACGT".to_vec();
+        unsafe { f(nibbles, len, &mut res) };
+        res
+    }
+
+    #[test]
+    fn test_encode_lut() {
+        let (nuc, res) = long_iupac();
+
+        for i in 0..nuc.len() {
+            assert_eq!(
+                encode_with(encode_lut, &nuc[..i]),
+                nibbles(&iupac_nibbles().repeat(7)[..i])
+            );
+        }
+        assert_eq!(encode_with(encode_lut, &nuc), res);
+    }
+
+    #[test]
+    fn test_encode_avx() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                let (nuc, res) = long_iupac();
+
+                for i in 0..nuc.len() {
+                    assert_eq!(
+                        encode_with(encode_shuffle_avx, &nuc[..i]),
+                        nibbles(&iupac_nibbles().repeat(7)[..i])
+                    );
+                }
+                assert_eq!(encode_with(encode_shuffle_avx, &nuc), res);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_sse() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("ssse3") {
+                let (nuc, res) = long_iupac();
+
+                for i in 0..nuc.len() {
+                    assert_eq!(
+                        encode_with(encode_shuffle_sse, &nuc[..i]),
+                        nibbles(&iupac_nibbles().repeat(7)[..i])
+                    );
+                }
+                assert_eq!(encode_with(encode_shuffle_sse, &nuc), res);
+            }
+        }
+    }
+
+    fn decoded(len: usize) -> Vec<u8> {
+        iupac_nibbles().repeat(7)[..len]
+            .iter()
+            .map(|&n| NUC_LUT[n as usize])
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_lut() {
+        let (nuc, res) = long_iupac();
+
+        for i in 0..=nuc.len() {
+            assert_eq!(decode_with(decode_lut, &res, i), decoded(i));
+        }
+    }
+
+    #[test]
+    fn test_decode_avx() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                let (nuc, res) = long_iupac();
+
+                for i in 0..=nuc.len() {
+                    assert_eq!(decode_with(decode_shuffle_avx, &res, i), decoded(i));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_sse() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("ssse3") {
+                let (nuc, res) = long_iupac();
+
+                for i in 0..=nuc.len() {
+                    assert_eq!(decode_with(decode_shuffle_sse, &res, i), decoded(i));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        assert_eq!(
+            decode(&encode(b"ACGTRYKMSWBDHVN-"), 16),
+            b"ACGTRYKMSWBDHVN-"
+        );
+        assert_eq!(decode(&encode(b"acgtuX?"), 7), b"ACGTTN-");
+    }
+
+    #[test]
+    #[should_panic(expected = "The length 17 is greater than the number of nucleotides!")]
+    fn test_decode_too_long() {
+        decode(&encode(b"ACGT"), 17);
+    }
+
+    #[test]
+    fn test_complement() {
+        let nibbles = encode(b"ACGTRYKMSWBDHVN-");
+
+        assert_eq!(decode(&complement(&nibbles), 16), b"TGCAYRMKSWVHDBN-");
+
+        let mut in_place = nibbles.clone();
+        complement_in_place(&mut in_place);
+        complement_in_place(&mut in_place);
+        assert_eq!(in_place, nibbles);
+    }
+
+    #[test]
+    fn test_to_bits() {
+        let nuc = b"ACGTTGCAACGTAAGGCCTTATATGCGCACGTACGTAGGCTTAA";
+        let nibbles = encode(nuc);
+
+        for i in 0..=nuc.len() {
+            assert_eq!(
+                to_bits(&nibbles, i),
+                Some(crate::nuc2bit::encode(&nuc[..i]))
+            );
+        }
+
+        assert_eq!(to_bits(&encode(b"ACGTACGTACGTACGTACGN"), 20), None);
+        assert_eq!(
+            to_bits(&encode(b"ACGTACGTACGTACGTACGN"), 19),
+            Some(crate::nuc2bit::encode(b"ACGTACGTACGTACGTACG"))
+        );
+        assert_eq!(to_bits(&encode(b"AC-T"), 4), None);
+        assert_eq!(to_bits(&encode(b"ACRT"), 4), None);
+    }
+
+    #[test]
+    fn test_from_bits() {
+        let nuc = b"ACGTTGCAACGTAAGGCCTTATATGCGCACGTACGTAGGCTTAA";
+        let bits = crate::nuc2bit::encode(nuc);
+
+        for i in 0..=nuc.len() {
+            assert_eq!(from_bits(&bits, i), encode(&nuc[..i]));
+        }
+    }
+}
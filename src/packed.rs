@@ -0,0 +1,437 @@
+use crate::bit2nuc::{decode, decode_at, Decode};
+use crate::complement::{complement, complement_in_place, reverse_complement};
+use crate::edit_distance::edit_distance;
+use crate::hamming::{hamming, hamming_at};
+use crate::kmer::Kmers;
+use crate::minimizer::{Minimizers, Order};
+use crate::nuc2bit::{encode, try_encode, EncodeError};
+use crate::popcount::popcount;
+use crate::utils::word_at;
+
+use std::ops::Range;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PackedSeq {
+    bits: Vec<u64>,
+    len: usize,
+}
+
+impl PackedSeq {
+    pub fn encode(nuc: &[u8]) -> Self {
+        PackedSeq::from_bits(encode(nuc), nuc.len())
+    }
+
+    pub fn try_encode(nuc: &[u8]) -> Result<Self, EncodeError> {
+        Ok(PackedSeq::from_bits(try_encode(nuc)?, nuc.len()))
+    }
+
+    pub fn from_bits(mut bits: Vec<u64>, len: usize) -> Self {
+        if len > (bits.len() * 32) {
+            panic!(
+                "The length {} is greater than the number of nucleotides!",
+                len
+            );
+        }
+
+        bits.truncate((len / 32) + if len % 32 == 0 { 0 } else { 1 });
+
+        // keep the unused bits of the last word cleared, so that two sequences with the same
+        // nucleotides always have the same words
+        let leftover = (len % 32) * 2;
+
+        if leftover > 0 {
+            let last = bits.len() - 1;
+            bits[last] &= (1 << leftover) - 1;
+        }
+
+        PackedSeq { bits, len }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn bits(&self) -> &[u64] {
+        &self.bits
+    }
+
+    pub fn into_bits(self) -> Vec<u64> {
+        self.bits
+    }
+
+    pub fn decode(&self) -> Vec<u8> {
+        decode(&self.bits, self.len)
+    }
+
+    pub fn complement(&self) -> PackedSeq {
+        PackedSeq::from_bits(complement(&self.bits), self.len)
+    }
+
+    pub fn complement_in_place(&mut self) {
+        complement_in_place(&mut self.bits);
+
+        // the unused bits of the last word were flipped too
+        let leftover = (self.len % 32) * 2;
+
+        if leftover > 0 {
+            let last = self.bits.len() - 1;
+            self.bits[last] &= (1 << leftover) - 1;
+        }
+    }
+
+    pub fn reverse_complement(&self) -> PackedSeq {
+        PackedSeq::from_bits(reverse_complement(&self.bits, self.len), self.len)
+    }
+
+    pub fn hamming(&self, other: &PackedSeq) -> usize {
+        if self.len != other.len {
+            panic!(
+                "The lengths {} and {} of the sequences are not equal!",
+                self.len, other.len
+            );
+        }
+
+        hamming(&self.bits, &other.bits, self.len)
+    }
+
+    pub fn edit_distance(&self, other: &PackedSeq) -> usize {
+        edit_distance(&self.bits, self.len, &other.bits, other.len)
+    }
+
+    pub fn iter(&self) -> Decode<'_> {
+        Decode::new(&self.bits, self.len)
+    }
+
+    pub fn kmers(&self, k: usize) -> Kmers<'_> {
+        Kmers::new(&self.bits, self.len, k)
+    }
+
+    pub fn minimizers(&self, k: usize, w: usize, order: Order) -> Minimizers<'_> {
+        Minimizers::new(&self.bits, self.len, k, w, order)
+    }
+
+    pub fn as_slice(&self) -> PackedSlice<'_> {
+        PackedSlice::new(&self.bits, 0, self.len)
+    }
+
+    pub fn slice(&self, range: Range<usize>) -> PackedSlice<'_> {
+        self.as_slice().slice(range)
+    }
+}
+
+impl<'a> IntoIterator for &'a PackedSeq {
+    type Item = u8;
+    type IntoIter = Decode<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PackedSlice<'a> {
+    // starts at the word that holds the first nucleotide
+    bits: &'a [u64],
+    // nucleotide offset of the first nucleotide in `bits[0]`, always lower than 32
+    offset: usize,
+    len: usize,
+}
+
+impl<'a> PackedSlice<'a> {
+    pub fn new(bits: &'a [u64], offset: usize, len: usize) -> Self {
+        if offset + len > (bits.len() * 32) {
+            panic!(
+                "The range {}..{} is out of the {} nucleotides!",
+                offset,
+                offset + len,
+                bits.len() * 32
+            );
+        }
+
+        let end = offset + len;
+        let end_idx = (end / 32) + if end % 32 == 0 { 0 } else { 1 };
+
+        PackedSlice {
+            bits: &bits[(offset / 32).min(end_idx)..end_idx],
+            offset: offset % 32,
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn bits(&self) -> &'a [u64] {
+        self.bits
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn slice(&self, range: Range<usize>) -> PackedSlice<'a> {
+        if range.start > range.end || range.end > self.len {
+            panic!(
+                "The range {}..{} is out of the {} nucleotides!",
+                range.start, range.end, self.len
+            );
+        }
+
+        PackedSlice::new(self.bits, self.offset + range.start, range.end - range.start)
+    }
+
+    pub fn to_packed(&self) -> PackedSeq {
+        let words = (self.len / 32) + if self.len % 32 == 0 { 0 } else { 1 };
+        let bits = (0..words)
+            .map(|i| word_at(self.bits, self.offset + i * 32))
+            .collect();
+
+        PackedSeq::from_bits(bits, self.len)
+    }
+
+    pub fn decode(&self) -> Vec<u8> {
+        decode_at(self.bits, self.offset, self.len)
+    }
+
+    pub fn complement(&self) -> PackedSeq {
+        let words = (self.len / 32) + if self.len % 32 == 0 { 0 } else { 1 };
+        let bits = (0..words)
+            .map(|i| word_at(self.bits, self.offset + i * 32) ^ 0xAAAAAAAAAAAAAAAAu64)
+            .collect();
+
+        PackedSeq::from_bits(bits, self.len)
+    }
+
+    pub fn reverse_complement(&self) -> PackedSeq {
+        // the nucleotides before the slice in the first word end up after it, once reversed
+        let bits = reverse_complement(self.bits, self.offset + self.len);
+
+        PackedSeq::from_bits(bits, self.len)
+    }
+
+    pub fn hamming(&self, other: &PackedSlice) -> usize {
+        if self.len != other.len {
+            panic!(
+                "The lengths {} and {} of the sequences are not equal!",
+                self.len, other.len
+            );
+        }
+
+        hamming_at(self.bits, self.offset, other.bits, other.offset, self.len)
+    }
+
+    pub fn popcount(&self) -> usize {
+        if self.offset == 0 {
+            return popcount(self.bits, self.len * 2);
+        }
+
+        // popcount does not care about nucleotides, only the first word needs to be shifted
+        let head = (64 - self.offset * 2).min(self.len * 2);
+
+        popcount(&[self.bits[0] >> (self.offset * 2)], head)
+            + popcount(&self.bits[1..], self.len * 2 - head)
+    }
+
+    pub fn iter(&self) -> Decode<'a> {
+        Decode::with_offset(self.bits, self.offset, self.len)
+    }
+}
+
+impl<'a, 'b> PartialEq<PackedSlice<'b>> for PackedSlice<'a> {
+    fn eq(&self, other: &PackedSlice<'b>) -> bool {
+        self.len == other.len && self.hamming(other) == 0
+    }
+}
+
+impl<'a> Eq for PackedSlice<'a> {}
+
+impl<'a> IntoIterator for PackedSlice<'a> {
+    type Item = u8;
+    type IntoIter = Decode<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packed_seq_encode() {
+        let seq = PackedSeq::encode(b"AUCGATCGATCGATCGATCGATCGATCGATCGATCG");
+
+        assert_eq!(seq.len(), 36);
+        assert_eq!(seq.is_empty(), false);
+        assert_eq!(
+            seq.bits(),
+            &[0b1101100011011000110110001101100011011000110110001101100011011000, 0b11011000]
+        );
+        assert_eq!(seq.decode(), b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG".to_vec());
+    }
+
+    #[test]
+    fn test_packed_seq_try_encode() {
+        assert_eq!(PackedSeq::try_encode(b"ATCG"), Ok(PackedSeq::encode(b"ATCG")));
+        assert_eq!(
+            PackedSeq::try_encode(b"ATCGN"),
+            Err(EncodeError { byte: b'N', pos: 4 })
+        );
+    }
+
+    #[test]
+    fn test_packed_seq_from_bits() {
+        let seq = PackedSeq::from_bits(vec![0xFFFFFFFFFFFFFFD8, 0xFFFF], 4);
+
+        assert_eq!(seq.bits(), &[0b11011000]);
+        assert_eq!(seq, PackedSeq::encode(b"ATCG"));
+        assert_ne!(seq, PackedSeq::encode(b"ATCGA"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_packed_seq_from_bits_too_long() {
+        PackedSeq::from_bits(vec![0], 33);
+    }
+
+    #[test]
+    fn test_packed_seq_complement() {
+        assert_eq!(
+            PackedSeq::encode(b"AUCGATCGATCGATCGATCGATCGATCGATCGATCG").complement(),
+            PackedSeq::encode(b"TAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGC")
+        );
+        assert_eq!(
+            PackedSeq::encode(b"ATCG").complement().bits(),
+            &[0b01110010]
+        );
+
+        let mut seq = PackedSeq::encode(b"ATCGA");
+        seq.complement_in_place();
+
+        assert_eq!(seq, PackedSeq::encode(b"TAGCT"));
+    }
+
+    #[test]
+    fn test_packed_seq_reverse_complement() {
+        assert_eq!(
+            PackedSeq::encode(b"AACGTTGCATCGATCGATCGATCGATCGATCGGTCA").reverse_complement(),
+            PackedSeq::encode(b"TGACCGATCGATCGATCGATCGATCGATGCAACGTT")
+        );
+        assert_eq!(
+            PackedSeq::encode(b"ATCGG").reverse_complement(),
+            PackedSeq::encode(b"CCGAT")
+        );
+    }
+
+    #[test]
+    fn test_packed_seq_hamming() {
+        let a = PackedSeq::encode(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+        let b = PackedSeq::encode(b"ATCGATCGATCGTTCGATCGATCGATCGATCGATCC");
+
+        assert_eq!(a.hamming(&b), 2);
+        assert_eq!(a.hamming(&a), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_packed_seq_hamming_len() {
+        PackedSeq::encode(b"ATCG").hamming(&PackedSeq::encode(b"ATC"));
+    }
+
+    #[test]
+    fn test_packed_seq_iterator() {
+        let seq = PackedSeq::encode(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+
+        assert_eq!(
+            seq.iter().collect::<Vec<u8>>(),
+            b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG".to_vec()
+        );
+        assert_eq!((&seq).into_iter().count(), 36);
+    }
+
+    #[test]
+    fn test_packed_slice_new() {
+        let seq = PackedSeq::encode(b"ATCGATCGATCGATCGATCGATCGATCGATCGTTTTGGGG");
+        let slice = seq.slice(34..38);
+
+        assert_eq!(slice.len(), 4);
+        assert_eq!(slice.offset(), 2);
+        assert_eq!(slice.bits().len(), 1);
+        assert_eq!(slice.decode(), b"TTGG");
+
+        let slice = seq.slice(17..35);
+
+        assert_eq!(slice.offset(), 17);
+        assert_eq!(slice.bits().len(), 2);
+        assert_eq!(slice.decode(), b"TCGATCGATCGATCGTTT");
+        assert_eq!(slice.slice(15..18).decode(), b"TTT");
+
+        assert_eq!(seq.slice(40..40).is_empty(), true);
+        assert_eq!(seq.slice(40..40).decode(), b"");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_packed_slice_out_of_range() {
+        PackedSeq::encode(b"ATCG").slice(2..5);
+    }
+
+    #[test]
+    fn test_packed_slice_to_packed() {
+        let seq = PackedSeq::encode(b"ATCGATCGATCGATCGATCGATCGATCGATCGTTTTGGGG");
+
+        assert_eq!(seq.slice(17..40).to_packed(), PackedSeq::encode(b"TCGATCGATCGATCGTTTTGGGG"));
+        assert_eq!(seq.slice(17..40).complement(), PackedSeq::encode(b"AGCTAGCTAGCTAGCAAAACCCC"));
+        assert_eq!(seq.as_slice().to_packed(), seq);
+        assert_eq!(
+            seq.slice(17..40).reverse_complement(),
+            PackedSeq::encode(b"CCCCAAAACGATCGATCGATCGA")
+        );
+    }
+
+    #[test]
+    fn test_packed_slice_hamming() {
+        let a = PackedSeq::encode(b"GGGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+        let b = PackedSeq::encode(b"TATCGATCGATCGATCGTTCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCC");
+
+        // different offsets in the first word
+        assert_eq!(a.slice(3..59).hamming(&b.slice(1..57)), 1);
+        assert_eq!(a.slice(3..59).hamming(&b.slice(5..61)), 2);
+        assert_eq!(a.slice(3..19), b.slice(1..17));
+        assert_ne!(a.slice(3..59), b.slice(5..61));
+
+        // same offsets in the first word
+        assert_eq!(a.slice(35..59).hamming(&a.slice(3..27)), 0);
+        assert_eq!(a.slice(3..59).hamming(&b.slice(3..59)), 56);
+    }
+
+    #[test]
+    fn test_packed_slice_popcount() {
+        let seq = PackedSeq::encode(b"GGGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+
+        assert_eq!(seq.slice(3..43).popcount(), 40);
+        assert_eq!(seq.slice(0..3).popcount(), 6);
+        assert_eq!(seq.slice(1..2).popcount(), 2);
+    }
+
+    #[test]
+    fn test_packed_slice_iterator() {
+        let seq = PackedSeq::encode(b"ATCGATCGATCGATCGATCGATCGATCGATCGTTTTGGGG");
+
+        assert_eq!(
+            seq.slice(30..38).iter().collect::<Vec<u8>>(),
+            b"CGTTTTGG".to_vec()
+        );
+        assert_eq!(seq.slice(30..38).into_iter().count(), 8);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::kmer::{Kmer, Kmers};
+use crate::minimizer::hash;
+
+use std::collections::VecDeque;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Syncmer {
+    pub pos: usize,
+    pub kmer: Kmer,
+}
+
+// k-mers whose smallest s-mer is at the start or the end (closed) or at offset `t` (open), the
+// s-mers are ordered by the hash of their canonical form so closed syncmers do not depend on the
+// strand
+pub struct Syncmers<'a> {
+    kmers: Kmers<'a>,
+    smers: Kmers<'a>,
+    // s-mers in a k-mer
+    w: usize,
+    open: Option<usize>,
+    pos: usize,
+    // keys of the last `w` s-mers
+    keys: Vec<u64>,
+    // increasing keys of the s-mers that can still be the smallest of a k-mer
+    queue: VecDeque<(u64, usize)>,
+}
+
+impl<'a> Syncmers<'a> {
+    pub fn closed(bits: &'a [u64], len: usize, k: usize, s: usize) -> Self {
+        Syncmers::new(bits, len, k, s, None)
+    }
+
+    pub fn open(bits: &'a [u64], len: usize, k: usize, s: usize, t: usize) -> Self {
+        if s <= k && t > k - s {
+            panic!("The offset {} is greater than {}!", t, k - s);
+        }
+
+        Syncmers::new(bits, len, k, s, Some(t))
+    }
+
+    fn new(bits: &'a [u64], len: usize, k: usize, s: usize, open: Option<usize>) -> Self {
+        if s == 0 || s > k {
+            panic!("The s-mer size {} is not between 1 and {}!", s, k);
+        }
+
+        Syncmers {
+            kmers: Kmers::new(bits, len, k),
+            smers: Kmers::new(bits, len, s),
+            w: k - s + 1,
+            open,
+            pos: 0,
+            keys: vec![0; k - s + 1],
+            queue: VecDeque::with_capacity(k - s + 1),
+        }
+    }
+}
+
+impl<'a> Iterator for Syncmers<'a> {
+    type Item = Syncmer;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let smer = self.smers.next()?;
+            let j = self.pos;
+            self.pos += 1;
+
+            let key = hash(smer.canonical);
+            self.keys[j % self.w] = key;
+
+            while let Some(&(back, _)) = self.queue.back() {
+                if back <= key {
+                    break;
+                }
+
+                self.queue.pop_back();
+            }
+            self.queue.push_back((key, j));
+
+            while let Some(&(_, front)) = self.queue.front() {
+                if front + self.w > j {
+                    break;
+                }
+
+                self.queue.pop_front();
+            }
+
+            if j + 1 < self.w {
+                continue;
+            }
+
+            // the k-mer starting at `i` is made of the s-mers `i..=j`
+            let i = j + 1 - self.w;
+            let kmer = self.kmers.next()?;
+            let min = self.queue[0].0;
+
+            let selected = match self.open {
+                Some(t) => self.keys[(i + t) % self.w] == min,
+                None => self.keys[i % self.w] == min || key == min,
+            };
+
+            if selected {
+                return Some(Syncmer { pos: i, kmer });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nuc2bit::encode;
+
+    const NUC: &[u8] =
+        b"ACGTTGCAACGTAAGGCCTTATATGCGCACGTACGTAGGCTTAAACCCGGGTTTAGCTTGACCAGTCAGTTTTTGCAAAAAA";
+
+    fn naive(nuc: &[u8], k: usize, s: usize, open: Option<usize>) -> Vec<usize> {
+        let bits = encode(nuc);
+        let keys = Kmers::new(&bits, nuc.len(), s)
+            .map(|smer| hash(smer.canonical))
+            .collect::<Vec<_>>();
+
+        keys.windows(k - s + 1)
+            .enumerate()
+            .filter(|(_, w)| {
+                let min = *w.iter().min().unwrap();
+
+                match open {
+                    Some(t) => w[t] == min,
+                    None => w[0] == min || w[k - s] == min,
+                }
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    #[test]
+    fn test_closed_syncmers() {
+        let bits = encode(NUC);
+        let kmers = Kmers::new(&bits, NUC.len(), 11).collect::<Vec<_>>();
+
+        for k in &[1, 2, 5, 11, 32] {
+            for s in 1..=*k {
+                let res = Syncmers::closed(&bits, NUC.len(), *k, s).collect::<Vec<_>>();
+
+                assert_eq!(
+                    res.iter().map(|m| m.pos).collect::<Vec<_>>(),
+                    naive(NUC, *k, s, None)
+                );
+                if *k == 11 {
+                    assert!(res.iter().all(|m| m.kmer == kmers[m.pos]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_open_syncmers() {
+        let bits = encode(NUC);
+
+        for k in &[1, 5, 11, 32] {
+            for s in 1..=*k {
+                for t in 0..=(*k - s) {
+                    let res = Syncmers::open(&bits, NUC.len(), *k, s, t)
+                        .map(|m| m.pos)
+                        .collect::<Vec<_>>();
+                    assert_eq!(res, naive(NUC, *k, s, Some(t)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_closed_syncmers_strand() {
+        let rc = NUC
+            .iter()
+            .rev()
+            .map(|n| match n {
+                b'A' => b'T',
+                b'C' => b'G',
+                b'G' => b'C',
+                _ => b'A',
+            })
+            .collect::<Vec<u8>>();
+
+        let mut forward = Syncmers::closed(&encode(NUC), NUC.len(), 15, 5)
+            .map(|m| m.kmer.canonical)
+            .collect::<Vec<_>>();
+        let mut reverse = Syncmers::closed(&encode(&rc), NUC.len(), 15, 5)
+            .map(|m| m.kmer.canonical)
+            .collect::<Vec<_>>();
+
+        forward.sort();
+        reverse.sort();
+        assert_eq!(forward, reverse);
+    }
+
+    #[test]
+    fn test_syncmers_short() {
+        let bits = encode(b"ACGTA");
+
+        assert_eq!(Syncmers::closed(&bits, 5, 6, 2).count(), 0);
+        assert_eq!(Syncmers::closed(&bits, 5, 5, 5).count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "The offset 4 is greater than 3!")]
+    fn test_open_syncmers_offset() {
+        Syncmers::open(&[0], 32, 5, 2, 4);
+    }
+}
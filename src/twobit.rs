@@ -0,0 +1,597 @@
+use crate::bit2nuc::{apply_mask_blocks, decode, decode_at, insert_n_blocks};
+use crate::nuc2bit::{mask_blocks, n_blocks, Block};
+use crate::packed::PackedSeq;
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+
+const SIGNATURE: u32 = 0x1A412743;
+
+// UCSC .2bit packs 4 nucleotides per byte, first nucleotide in the high bits, with T=00, C=01,
+// A=10, G=11
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TwoBitRecord {
+    pub name: String,
+    pub seq: PackedSeq,
+    pub n_blocks: Vec<Block>,
+    pub mask_blocks: Vec<Block>,
+}
+
+impl TwoBitRecord {
+    pub fn from_ascii(name: &str, nuc: &[u8]) -> Self {
+        TwoBitRecord {
+            name: name.to_string(),
+            seq: PackedSeq::encode(nuc),
+            n_blocks: n_blocks(nuc),
+            mask_blocks: mask_blocks(nuc),
+        }
+    }
+
+    pub fn to_ascii(&self) -> Vec<u8> {
+        let mut nuc = decode(self.seq.bits(), self.seq.len());
+        insert_n_blocks(&mut nuc, &self.n_blocks);
+        apply_mask_blocks(&mut nuc, &self.mask_blocks);
+        nuc
+    }
+}
+
+pub fn write<W: Write>(mut out: W, records: &[TwoBitRecord]) -> io::Result<()> {
+    // nothing is written if a record cannot be stored
+    check_u32(records.len(), "number of records")?;
+
+    for record in records {
+        check_record(record)?;
+    }
+
+    let index_len = records.iter().map(|r| 1 + r.name.len() + 4).sum::<usize>();
+    let records_len = records.iter().map(record_len).sum::<usize>();
+
+    // offsets only need 64 bits if the file is larger than 4GiB
+    let version = if 16 + index_len + records_len > u32::MAX as usize { 1 } else { 0 };
+    let offset_len = if version == 1 { 8 } else { 4 };
+
+    out.write_all(&SIGNATURE.to_le_bytes())?;
+    out.write_all(&(version as u32).to_le_bytes())?;
+    out.write_all(&(records.len() as u32).to_le_bytes())?;
+    out.write_all(&0u32.to_le_bytes())?;
+
+    let mut offset = 16 + records.iter().map(|r| 1 + r.name.len() + offset_len).sum::<usize>();
+
+    for record in records {
+        out.write_all(&[record.name.len() as u8])?;
+        out.write_all(record.name.as_bytes())?;
+
+        if version == 1 {
+            out.write_all(&(offset as u64).to_le_bytes())?;
+        } else {
+            out.write_all(&(offset as u32).to_le_bytes())?;
+        }
+
+        offset += record_len(record);
+    }
+
+    for record in records {
+        out.write_all(&(record.seq.len() as u32).to_le_bytes())?;
+        write_blocks(&mut out, &record.n_blocks)?;
+        write_blocks(&mut out, &record.mask_blocks)?;
+        out.write_all(&0u32.to_le_bytes())?;
+        out.write_all(&bits_to_twobit(record.seq.bits(), record.seq.len()))?;
+    }
+
+    Ok(())
+}
+
+fn check_record(record: &TwoBitRecord) -> io::Result<()> {
+    if record.name.len() > 255 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("The name {} is longer than 255 bytes!", record.name),
+        ));
+    }
+
+    check_u32(record.seq.len(), "length")?;
+
+    for blocks in &[&record.n_blocks, &record.mask_blocks] {
+        check_u32(blocks.len(), "number of blocks")?;
+
+        for block in blocks.iter() {
+            check_u32(block.start, "block start")?;
+            check_u32(block.len, "block length")?;
+
+            // the reader rejects blocks that do not fit in the sequence
+            let end = block.start.checked_add(block.len).filter(|&end| end <= record.seq.len());
+            if end.is_none() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "The block starting at {} of length {} is out of the {} nucleotides of {}!",
+                        block.start,
+                        block.len,
+                        record.seq.len(),
+                        record.name
+                    ),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// the fields of a .2bit file are 32 bits
+fn check_u32(value: usize, what: &str) -> io::Result<()> {
+    if value > u32::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("The {} {} does not fit in 32 bits!", what, value),
+        ));
+    }
+
+    Ok(())
+}
+
+fn record_len(record: &TwoBitRecord) -> usize {
+    4 + 4 + record.n_blocks.len() * 8 + 4 + record.mask_blocks.len() * 8 + 4 + packed_len(record.seq.len())
+}
+
+// bytes holding `len` nucleotides
+fn packed_len(len: usize) -> usize {
+    (len / 4) + if len % 4 == 0 { 0 } else { 1 }
+}
+
+fn write_blocks<W: Write>(out: &mut W, blocks: &[Block]) -> io::Result<()> {
+    out.write_all(&(blocks.len() as u32).to_le_bytes())?;
+
+    for block in blocks {
+        out.write_all(&(block.start as u32).to_le_bytes())?;
+    }
+
+    for block in blocks {
+        out.write_all(&(block.len as u32).to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+pub struct TwoBitReader<R> {
+    reader: R,
+    swapped: bool,
+    index: Vec<(String, u64)>,
+}
+
+struct RecordHeader {
+    len: usize,
+    n_blocks: Vec<Block>,
+    mask_blocks: Vec<Block>,
+    data: u64,
+}
+
+impl<R: Read + Seek> TwoBitReader<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0u8; 16];
+        read_exact(&mut reader, &mut header)?;
+
+        let signature = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let swapped = if signature == SIGNATURE {
+            false
+        } else if signature.swap_bytes() == SIGNATURE {
+            true
+        } else {
+            return Err(invalid_data(format!("Invalid .2bit signature {:#010x}!", signature)));
+        };
+
+        let version = to_u32([header[4], header[5], header[6], header[7]], swapped);
+        if version > 1 {
+            return Err(invalid_data(format!("Unsupported .2bit version {}!", version)));
+        }
+
+        let count = to_u32([header[8], header[9], header[10], header[11]], swapped);
+        // the counts come from the file, they are not trusted to size allocations
+        let mut index = Vec::new();
+
+        for _ in 0..count {
+            let mut name_len = [0u8; 1];
+            read_exact(&mut reader, &mut name_len)?;
+
+            let mut name = vec![0u8; name_len[0] as usize];
+            read_exact(&mut reader, &mut name)?;
+            let name = String::from_utf8(name).map_err(|e| invalid_data(e.to_string()))?;
+
+            let offset = if version == 1 {
+                read_u64(&mut reader, swapped)?
+            } else {
+                read_u32(&mut reader, swapped)? as u64
+            };
+
+            index.push((name, offset));
+        }
+
+        Ok(TwoBitReader { reader, swapped, index })
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.index.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn len(&mut self, name: &str) -> io::Result<usize> {
+        let offset = self.offset(name)?;
+        self.reader.seek(SeekFrom::Start(offset))?;
+        Ok(read_u32(&mut self.reader, self.swapped)? as usize)
+    }
+
+    pub fn record(&mut self, name: &str) -> io::Result<TwoBitRecord> {
+        let header = self.record_header(name)?;
+
+        let mut bytes = vec![0u8; packed_len(header.len)];
+        read_exact(&mut self.reader, &mut bytes)?;
+
+        Ok(TwoBitRecord {
+            name: name.to_string(),
+            seq: PackedSeq::from_bits(twobit_to_bits(&bytes), header.len),
+            n_blocks: header.n_blocks,
+            mask_blocks: header.mask_blocks,
+        })
+    }
+
+    pub fn sequence(&mut self, name: &str) -> io::Result<Vec<u8>> {
+        Ok(self.record(name)?.to_ascii())
+    }
+
+    // only the bytes that hold the region are read
+    pub fn region(&mut self, name: &str, range: Range<usize>) -> io::Result<Vec<u8>> {
+        let header = self.record_header(name)?;
+
+        if range.start > range.end || range.end > header.len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "The range {}..{} is out of the {} nucleotides!",
+                    range.start, range.end, header.len
+                ),
+            ));
+        }
+
+        let first = range.start / 4;
+        let mut bytes = vec![0u8; packed_len(range.end) - first];
+        self.reader.seek(SeekFrom::Start(header.data + first as u64))?;
+        read_exact(&mut self.reader, &mut bytes)?;
+
+        let mut nuc = decode_at(&twobit_to_bits(&bytes), range.start % 4, range.end - range.start);
+        insert_n_blocks(&mut nuc, &clip_blocks(&header.n_blocks, &range));
+        apply_mask_blocks(&mut nuc, &clip_blocks(&header.mask_blocks, &range));
+
+        Ok(nuc)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn offset(&self, name: &str) -> io::Result<u64> {
+        self.index
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|&(_, offset)| offset)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No sequence named {}!", name)))
+    }
+
+    fn record_header(&mut self, name: &str) -> io::Result<RecordHeader> {
+        let offset = self.offset(name)?;
+        self.reader.seek(SeekFrom::Start(offset))?;
+
+        let len = read_u32(&mut self.reader, self.swapped)? as usize;
+        let n_blocks = read_blocks(&mut self.reader, self.swapped)?;
+        let mask_blocks = read_blocks(&mut self.reader, self.swapped)?;
+        read_u32(&mut self.reader, self.swapped)?;
+
+        for block in n_blocks.iter().chain(mask_blocks.iter()) {
+            if block.start + block.len > len {
+                return Err(invalid_data(format!(
+                    "The block {}..{} of {} is out of the {} nucleotides!",
+                    block.start,
+                    block.start + block.len,
+                    name,
+                    len
+                )));
+            }
+        }
+
+        let data = self.reader.stream_position()?;
+
+        Ok(RecordHeader {
+            len,
+            n_blocks,
+            mask_blocks,
+            data,
+        })
+    }
+}
+
+// blocks overlapping `range`, relative to its start
+fn clip_blocks(blocks: &[Block], range: &Range<usize>) -> Vec<Block> {
+    blocks
+        .iter()
+        .filter(|b| b.start < range.end && b.start + b.len > range.start)
+        .map(|b| {
+            let start = std::cmp::max(b.start, range.start);
+            let end = std::cmp::min(b.start + b.len, range.end);
+
+            Block {
+                start: start - range.start,
+                len: end - start,
+            }
+        })
+        .collect()
+}
+
+fn read_blocks<R: Read>(reader: &mut R, swapped: bool) -> io::Result<Vec<Block>> {
+    let count = read_u32(reader, swapped)? as usize;
+
+    let mut starts = Vec::new();
+    for _ in 0..count {
+        starts.push(read_u32(reader, swapped)? as usize);
+    }
+
+    let mut blocks = Vec::with_capacity(starts.len());
+    for start in starts {
+        blocks.push(Block {
+            start,
+            len: read_u32(reader, swapped)? as usize,
+        });
+    }
+
+    Ok(blocks)
+}
+
+// a truncated file is invalid data, not an unexpected end of file
+fn read_exact<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<()> {
+    reader.read_exact(buf).map_err(|e| {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            invalid_data("The .2bit file is truncated!".to_string())
+        } else {
+            e
+        }
+    })
+}
+
+fn read_u32<R: Read>(reader: &mut R, swapped: bool) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    read_exact(reader, &mut buf)?;
+    Ok(to_u32(buf, swapped))
+}
+
+fn read_u64<R: Read>(reader: &mut R, swapped: bool) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    read_exact(reader, &mut buf)?;
+
+    if swapped {
+        Ok(u64::from_be_bytes(buf))
+    } else {
+        Ok(u64::from_le_bytes(buf))
+    }
+}
+
+fn to_u32(buf: [u8; 4], swapped: bool) -> u32 {
+    if swapped {
+        u32::from_be_bytes(buf)
+    } else {
+        u32::from_le_bytes(buf)
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// convert between our codes and the .2bit codes, it is its own inverse: the low bit is kept and
+// the high bit is flipped when the low bit is 0 (A <-> T)
+#[inline]
+fn recode_word(w: u64) -> u64 {
+    w ^ ((!w & 0x5555555555555555) << 1)
+}
+
+// reverse the order of the 4 nucleotides in each byte
+#[inline]
+fn reverse_in_bytes(w: u64) -> u64 {
+    let w = ((w >> 4) & 0x0F0F0F0F0F0F0F0F) | ((w & 0x0F0F0F0F0F0F0F0F) << 4);
+    ((w >> 2) & 0x3333333333333333) | ((w & 0x3333333333333333) << 2)
+}
+
+fn twobit_to_bits(bytes: &[u8]) -> Vec<u64> {
+    bytes
+        .chunks(8)
+        .map(|chunk| {
+            let mut buf = [0u8; 8];
+            buf[..chunk.len()].copy_from_slice(chunk);
+            recode_word(reverse_in_bytes(u64::from_le_bytes(buf)))
+        })
+        .collect()
+}
+
+fn bits_to_twobit(bits: &[u64], len: usize) -> Vec<u8> {
+    let mut res = Vec::with_capacity(bits.len() * 8);
+
+    for &w in bits {
+        res.extend_from_slice(&reverse_in_bytes(recode_word(w)).to_le_bytes());
+    }
+
+    res.truncate(packed_len(len));
+
+    // the padding nucleotides of the last byte are T
+    if len % 4 != 0 {
+        if let Some(last) = res.last_mut() {
+            *last &= 0xFF << (8 - (len % 4) * 2);
+        }
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    fn records() -> Vec<TwoBitRecord> {
+        vec![
+            TwoBitRecord::from_ascii("chr1", b"NNNNacgtACGTTCAGnnACGTacgNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNGGa"),
+            TwoBitRecord::from_ascii("chrM", b"TCAG"),
+            TwoBitRecord::from_ascii("empty", b""),
+        ]
+    }
+
+    fn to_file(records: &[TwoBitRecord]) -> Vec<u8> {
+        let mut file = Vec::new();
+        write(&mut file, records).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_twobit_codes() {
+        assert_eq!(bits_to_twobit(PackedSeq::encode(b"TCAG").bits(), 4), vec![0b00011011]);
+        assert_eq!(bits_to_twobit(PackedSeq::encode(b"TCAGG").bits(), 5), vec![0b00011011, 0b11000000]);
+        assert_eq!(bits_to_twobit(PackedSeq::encode(b"A").bits(), 1), vec![0b10000000]);
+
+        let seq = PackedSeq::encode(b"ACGTTGCAACGTAAGGCCTTATATGCGCACGTACGTA");
+        let twobit = bits_to_twobit(seq.bits(), seq.len());
+        assert_eq!(PackedSeq::from_bits(twobit_to_bits(&twobit), seq.len()), seq);
+    }
+
+    #[test]
+    fn test_write() {
+        let file = to_file(&records()[1..2]);
+
+        assert_eq!(
+            file,
+            vec![
+                0x43, 0x27, 0x41, 0x1A, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, // header
+                4, b'c', b'h', b'r', b'M', 25, 0, 0, 0, // index
+                4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0b00011011 // record
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_write_invalid() {
+        let mut long_name = records()[1].clone();
+        long_name.name = "c".repeat(256);
+
+        let mut far_block = records()[1].clone();
+        far_block.n_blocks.push(Block { start: 1 << 32, len: 1 });
+
+        let mut long_block = records()[1].clone();
+        long_block.mask_blocks.push(Block { start: 0, len: 1 << 32 });
+
+        let mut past_end = records()[1].clone();
+        past_end.n_blocks.push(Block { start: 2, len: 3 });
+
+        let mut overflow = records()[1].clone();
+        overflow.mask_blocks.push(Block {
+            start: usize::MAX,
+            len: 1,
+        });
+
+        for invalid in vec![long_name, far_block, long_block, past_end, overflow] {
+            // the invalid record is last, so the valid ones must not be written either
+            let mut file = Vec::new();
+            let err = write(&mut file, &[records()[0].clone(), invalid]).unwrap_err();
+
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            assert!(file.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_read() {
+        let records = records();
+        let mut reader = TwoBitReader::new(Cursor::new(to_file(&records))).unwrap();
+
+        assert_eq!(reader.names().collect::<Vec<_>>(), vec!["chr1", "chrM", "empty"]);
+        assert_eq!(reader.len("chr1").unwrap(), 64);
+        assert_eq!(reader.len("empty").unwrap(), 0);
+
+        for record in &records {
+            assert_eq!(&reader.record(&record.name).unwrap(), record);
+        }
+
+        assert_eq!(
+            reader.sequence("chr1").unwrap(),
+            b"NNNNacgtACGTTCAGnnACGTacgNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNGGa".to_vec()
+        );
+        assert_eq!(reader.sequence("chrM").unwrap(), b"TCAG");
+        assert_eq!(reader.record("chrX").unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_region() {
+        let mut reader = TwoBitReader::new(Cursor::new(to_file(&records()))).unwrap();
+
+        assert_eq!(reader.region("chr1", 2..27).unwrap(), b"NNacgtACGTTCAGnnACGTacgNN");
+        assert_eq!(reader.region("chr1", 17..18).unwrap(), b"n");
+        assert_eq!(reader.region("chr1", 60..64).unwrap(), b"NGGa");
+        assert_eq!(reader.region("chrM", 1..3).unwrap(), b"CA");
+        assert_eq!(reader.region("chrM", 2..2).unwrap(), b"");
+        assert_eq!(reader.region("chrM", 2..5).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_read_swapped() {
+        let file = vec![
+            0x1A, 0x41, 0x27, 0x43, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, // header
+            1, b'a', 0, 0, 0, 22, // index
+            0, 0, 0, 6, // dnaSize
+            0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 2, // N blocks
+            0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 2, // mask blocks
+            0, 0, 0, 0, 0b00011011, 0b10110000, // reserved and DNA
+        ];
+        let mut reader = TwoBitReader::new(Cursor::new(file)).unwrap();
+
+        assert_eq!(reader.sequence("a").unwrap(), b"tcAGNN");
+    }
+
+    #[test]
+    fn test_read_version_1() {
+        let file = vec![
+            0x43, 0x27, 0x41, 0x1A, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, // header
+            1, b'a', 26, 0, 0, 0, 0, 0, 0, 0, // index
+            5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0b00011011, 0b11000000, // record
+        ];
+        let mut reader = TwoBitReader::new(Cursor::new(file)).unwrap();
+
+        assert_eq!(reader.sequence("a").unwrap(), b"TCAGG");
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        let err = TwoBitReader::new(Cursor::new(vec![0u8; 16])).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let err = TwoBitReader::new(Cursor::new(vec![0x43, 0x27, 0x41, 0x1A, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_read_truncated_index() {
+        let file = vec![0x43, 0x27, 0x41, 0x1A, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0];
+
+        let err = TwoBitReader::new(Cursor::new(file)).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_read_truncated_blocks() {
+        let file = vec![
+            0x43, 0x27, 0x41, 0x1A, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, // header
+            1, b'a', 22, 0, 0, 0, // index
+            4, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, // dnaSize and N block count
+        ];
+        let mut reader = TwoBitReader::new(Cursor::new(file)).unwrap();
+
+        assert_eq!(reader.record("a").err().unwrap().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(reader.sequence("a").err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+}
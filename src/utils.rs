@@ -20,3 +20,54 @@ pub fn encoding_equals(a_bits: &[u64], b_bits: &[u64], len: usize) -> bool {
 
     equals
 }
+
+// the 32 nucleotides starting at nucleotide `pos`, nucleotides past the end of `bits` are zeros
+#[inline]
+pub(crate) fn word_at(bits: &[u64], pos: usize) -> u64 {
+    let idx = pos / 32;
+    let shift = (pos % 32) * 2;
+    let lo = bits[idx] >> shift;
+
+    if shift == 0 || idx + 1 >= bits.len() {
+        lo
+    } else {
+        lo | (bits[idx + 1] << (64 - shift))
+    }
+}
+
+// gather the even bits of `x` in the low 32 bits
+#[inline]
+pub(crate) fn compact_even_bits(x: u64) -> u64 {
+    let x = x & 0x5555555555555555;
+    let x = (x | (x >> 1)) & 0x3333333333333333;
+    let x = (x | (x >> 2)) & 0x0F0F0F0F0F0F0F0F;
+    let x = (x | (x >> 4)) & 0x00FF00FF00FF00FF;
+    let x = (x | (x >> 8)) & 0x0000FFFF0000FFFF;
+    (x | (x >> 16)) & 0x00000000FFFFFFFF
+}
+
+// a pseudo-random sequence of `len` nucleotides, the same for the same `seed`
+#[cfg(test)]
+pub(crate) fn random_nuc(len: usize, seed: u64) -> Vec<u8> {
+    let mut x = seed;
+
+    (0..len)
+        .map(|_| {
+            x = x
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            b"ACGT"[(x >> 62) as usize]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_even_bits() {
+        assert_eq!(compact_even_bits(0x5555555555555555), 0xFFFFFFFF);
+        assert_eq!(compact_even_bits(0b0100010001), 0b10101);
+    }
+}